@@ -0,0 +1,414 @@
+use crate::ApollosKiosk;
+use crate::logging::MQTT_DATA;
+use crate::theme;
+use eframe::egui;
+use egui_material_icons::icons::*;
+use log::{info, warn};
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+/// Seconds per flash cycle for flashing alerts.
+const FLASH_PERIOD: f64 = 1.0;
+
+/// How long a fullscreen alert without an expiry stays up. A locked kiosk can't
+/// dismiss it, and it covers the title held down to unlock.
+const TAKEOVER_EXPIRY: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Severity {
+    #[default]
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    fn icon(self) -> &'static str {
+        match self {
+            Severity::Info => ICON_INFO,
+            Severity::Warning => ICON_WARNING,
+            Severity::Critical => ICON_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum AlertDisplay {
+    /// Strip above the card layout
+    Banner,
+    /// Covers the whole screen
+    Fullscreen,
+}
+
+/// An alert as published on the alerts topic.
+///
+/// `{"title": "...", "body": "...", "severity": "warning", "expires_in": 300}`
+/// shows an alert; `{"id": "...", "clear": true}` removes one and
+/// `{"clear": true}` removes them all.
+#[derive(Debug, Deserialize)]
+struct AlertMessage {
+    /// Alerts with the same id replace each other
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    body: String,
+    #[serde(default)]
+    severity: Severity,
+    /// Defaults to fullscreen for critical alerts and a banner otherwise
+    #[serde(default)]
+    display: Option<AlertDisplay>,
+    #[serde(default)]
+    flash: bool,
+    /// RFC 3339 time after which the alert is removed
+    #[serde(default)]
+    expires: Option<String>,
+    /// Seconds after arrival that the alert is removed; fullscreen alerts
+    /// without an expiry are removed after `TAKEOVER_EXPIRY`
+    #[serde(default)]
+    expires_in: Option<u64>,
+    #[serde(default)]
+    clear: bool,
+}
+
+impl AlertMessage {
+    fn display(&self) -> AlertDisplay {
+        self.display.unwrap_or(match self.severity {
+            Severity::Critical => AlertDisplay::Fullscreen,
+            _ => AlertDisplay::Banner,
+        })
+    }
+
+    /// When the alert should be removed, counting from `now`.
+    ///
+    /// Expiries too far away to represent count as none, so fullscreen alerts
+    /// still end after `TAKEOVER_EXPIRY`.
+    fn expires_at(&self, display: AlertDisplay, now: Instant) -> Option<Instant> {
+        let expires_at = match (&self.expires_in, &self.expires) {
+            (Some(secs), _) => now.checked_add(Duration::from_secs(*secs)),
+            (None, Some(expires)) => match chrono::DateTime::parse_from_rfc3339(expires) {
+                Ok(at) => {
+                    let remaining = (at.with_timezone(&chrono::Utc) - chrono::Utc::now())
+                        .to_std()
+                        .unwrap_or_default();
+                    now.checked_add(remaining)
+                }
+                Err(e) => {
+                    warn!(target: MQTT_DATA, "Invalid expiry '{}': {}", expires, e);
+                    None
+                }
+            },
+            (None, None) => None,
+        };
+
+        match (expires_at, display) {
+            (None, AlertDisplay::Fullscreen) => now.checked_add(TAKEOVER_EXPIRY),
+            (expires_at, _) => expires_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Alert {
+    id: Option<String>,
+    title: String,
+    body: String,
+    severity: Severity,
+    display: AlertDisplay,
+    flash: bool,
+    expires_at: Option<Instant>,
+}
+
+impl Alert {
+    fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|at| Instant::now() >= at)
+    }
+
+    fn color(&self, accent: egui::Color32) -> egui::Color32 {
+        match self.severity {
+            Severity::Info => accent,
+            Severity::Warning => egui::Color32::from_rgb(230, 140, 20),
+            Severity::Critical => egui::Color32::from_rgb(200, 40, 40),
+        }
+    }
+
+    /// Background color, pulsing between full and half strength when flashing.
+    fn background(&self, ctx: &egui::Context, accent: egui::Color32) -> egui::Color32 {
+        let color = self.color(accent);
+        if !self.flash {
+            return color;
+        }
+
+        let time = ctx.input(|i| i.time);
+        let pulse = 0.5 + 0.5 * (time * std::f64::consts::TAU / FLASH_PERIOD).sin() as f32;
+        ctx.request_repaint_after(Duration::from_millis(50));
+        color.gamma_multiply(0.5 + 0.5 * pulse)
+    }
+}
+
+/// Readable text color on top of an alert background.
+fn text_on(background: egui::Color32) -> egui::Color32 {
+    let [r, g, b, _] = background.to_array();
+    if theme::relative_luminance([r, g, b]) > 0.4 {
+        egui::Color32::BLACK
+    } else {
+        egui::Color32::WHITE
+    }
+}
+
+impl ApollosKiosk {
+    /// Handle a message received on the alerts topic.
+    pub(crate) fn handle_alert(&mut self, payload: &str) {
+        let message = match serde_json::from_str::<AlertMessage>(payload) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!(target: MQTT_DATA, "Ignoring malformed alert: {}", e);
+                return;
+            }
+        };
+
+        if message.clear {
+            match &message.id {
+                Some(id) => self.alerts.retain(|a| a.id.as_ref() != Some(id)),
+                None => self.alerts.clear(),
+            }
+            info!(
+                target: MQTT_DATA,
+                "Cleared {}",
+                message.id.as_deref().unwrap_or("all alerts")
+            );
+            return;
+        }
+
+        let display = message.display();
+        let expires_at = message.expires_at(display, Instant::now());
+
+        let alert = Alert {
+            display,
+            id: message.id,
+            title: message.title,
+            body: message.body,
+            severity: message.severity,
+            flash: message.flash,
+            expires_at,
+        };
+        if alert.is_expired() {
+            return;
+        }
+        info!(target: MQTT_DATA, "{:?} alert '{}'", alert.severity, alert.title);
+
+        // A new version of a queued alert takes its place in the queue
+        match alert
+            .id
+            .as_ref()
+            .and_then(|id| self.alerts.iter().position(|a| a.id.as_ref() == Some(id)))
+        {
+            Some(idx) => self.alerts[idx] = alert,
+            None => self.alerts.push_back(alert),
+        }
+    }
+
+    /// Drop expired alerts and wake up again when the next one expires.
+    pub(crate) fn update_alerts(&mut self, ctx: &egui::Context) {
+        self.alerts.retain(|a| !a.is_expired());
+
+        if let Some(next) = self.alerts.iter().filter_map(|a| a.expires_at).min() {
+            ctx.request_repaint_after(next.saturating_duration_since(Instant::now()));
+        }
+    }
+
+    fn alert_queue_label(&self) -> Option<String> {
+        (self.alerts.len() > 1).then(|| format!("+{} more", self.alerts.len() - 1))
+    }
+
+    /// Show the first queued alert as a strip above the card layout, if it is a banner.
+    pub(crate) fn render_alert_banner(&mut self, ctx: &egui::Context) {
+        let Some(alert) = self.alerts.front() else {
+            return;
+        };
+        if alert.display != AlertDisplay::Banner {
+            return;
+        }
+
+        let background = alert.background(ctx, self.get_current_theme().accent());
+        let text = text_on(background);
+        let queue_label = self.alert_queue_label();
+        let mut dismiss = false;
+
+        egui::TopBottomPanel::top("alert_banner")
+            .frame(
+                egui::Frame::NONE
+                    .fill(background)
+                    .inner_margin(egui::Margin::symmetric(16, 10)),
+            )
+            .show(ctx, |ui| {
+                let response = ui
+                    .horizontal(|ui| {
+                        ui.label(
+                            egui::RichText::new(alert.severity.icon())
+                                .size(28.0)
+                                .color(text),
+                        );
+                        ui.vertical(|ui| {
+                            ui.label(
+                                egui::RichText::new(&alert.title)
+                                    .heading()
+                                    .strong()
+                                    .color(text),
+                            );
+                            if !alert.body.is_empty() {
+                                ui.label(egui::RichText::new(&alert.body).color(text));
+                            }
+                        });
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            if !self.config.locked
+                                && ui
+                                    .button(egui::RichText::new(ICON_CLOSE).size(20.0))
+                                    .clicked()
+                            {
+                                dismiss = true;
+                            }
+                            if let Some(label) = &queue_label {
+                                ui.label(egui::RichText::new(label).color(text));
+                            }
+                        });
+                    })
+                    .response;
+
+                if !self.config.locked && response.interact(egui::Sense::click()).clicked() {
+                    dismiss = true;
+                }
+            });
+
+        if dismiss {
+            self.alerts.pop_front();
+        }
+    }
+
+    /// Show the first queued alert over everything else, if it is a fullscreen alert.
+    pub(crate) fn render_alert_takeover(&mut self, ctx: &egui::Context) {
+        let Some(alert) = self.alerts.front() else {
+            return;
+        };
+        if alert.display != AlertDisplay::Fullscreen {
+            return;
+        }
+
+        let background = alert.background(ctx, self.get_current_theme().accent());
+        let text = text_on(background);
+        let queue_label = self.alert_queue_label();
+        let scale = self.get_scale_factor(ctx);
+        let rect = ctx.content_rect();
+        let mut dismiss = false;
+
+        // Tooltip order keeps the alert above the night overlay and open windows
+        egui::Area::new(egui::Id::new("alert_takeover"))
+            .order(egui::Order::Tooltip)
+            .fixed_pos(rect.min)
+            .show(ctx, |ui| {
+                ui.set_min_size(rect.size());
+                ui.painter().rect_filled(rect, 0.0, background);
+
+                ui.vertical_centered(|ui| {
+                    ui.add_space(rect.height() / 4.0);
+                    ui.label(
+                        egui::RichText::new(alert.severity.icon())
+                            .size(140.0 * scale)
+                            .color(text),
+                    );
+                    ui.label(
+                        egui::RichText::new(&alert.title)
+                            .size(96.0 * scale)
+                            .strong()
+                            .color(text),
+                    );
+                    ui.add_space(24.0 * scale);
+                    ui.label(
+                        egui::RichText::new(&alert.body)
+                            .size(44.0 * scale)
+                            .color(text),
+                    );
+                    if let Some(label) = &queue_label {
+                        ui.add_space(24.0 * scale);
+                        ui.label(egui::RichText::new(label).size(28.0 * scale).color(text));
+                    }
+                    if !self.config.locked {
+                        ui.add_space(48.0 * scale);
+                        ui.label(
+                            egui::RichText::new("Tap to dismiss")
+                                .size(24.0 * scale)
+                                .color(text.gamma_multiply(0.7)),
+                        );
+                    }
+                });
+
+                let response = ui.interact(rect, ui.id().with("dismiss"), egui::Sense::click());
+                if !self.config.locked && response.clicked() {
+                    dismiss = true;
+                }
+            });
+
+        if dismiss {
+            self.alerts.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(json: &str) -> AlertMessage {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn expires_in_counts_from_arrival() {
+        let now = Instant::now();
+        let alert = message(r#"{"title": "Test", "expires_in": 300}"#);
+        assert_eq!(
+            alert.expires_at(alert.display(), now),
+            Some(now + Duration::from_secs(300))
+        );
+    }
+
+    #[test]
+    fn huge_expires_in_does_not_overflow() {
+        let now = Instant::now();
+        let banner = message(r#"{"title": "Test", "expires_in": 18446744073709551615}"#);
+        assert_eq!(banner.expires_at(banner.display(), now), None);
+
+        let takeover = message(
+            r#"{"title": "Test", "severity": "critical", "expires_in": 18446744073709551615}"#,
+        );
+        assert_eq!(
+            takeover.expires_at(takeover.display(), now),
+            Some(now + TAKEOVER_EXPIRY)
+        );
+    }
+
+    #[test]
+    fn fullscreen_alerts_expire_by_default() {
+        let now = Instant::now();
+        let takeover = message(r#"{"title": "Test", "severity": "critical"}"#);
+        assert_eq!(takeover.display(), AlertDisplay::Fullscreen);
+        assert_eq!(
+            takeover.expires_at(takeover.display(), now),
+            Some(now + TAKEOVER_EXPIRY)
+        );
+
+        let banner = message(r#"{"title": "Test", "severity": "warning"}"#);
+        assert_eq!(banner.display(), AlertDisplay::Banner);
+        assert_eq!(banner.expires_at(banner.display(), now), None);
+    }
+
+    #[test]
+    fn past_expiry_is_already_expired() {
+        let now = Instant::now();
+        let alert = message(r#"{"title": "Test", "expires": "2000-01-01T00:00:00Z"}"#);
+        assert_eq!(alert.expires_at(alert.display(), now), Some(now));
+    }
+}
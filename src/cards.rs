@@ -0,0 +1,185 @@
+use crate::ApollosKiosk;
+use crate::theme;
+use eframe::egui;
+use egui_material_icons::icons::*;
+use serde::{Deserialize, Serialize};
+
+/// Icons offered in the card editor, by the name stored in the config.
+pub(crate) const CARD_ICONS: &[(&str, &str)] = &[
+    ("bus", ICON_DIRECTIONS_BUS),
+    ("tram", ICON_TRAM),
+    ("subway", ICON_SUBWAY),
+    ("train", ICON_TRAIN),
+    ("ferry", ICON_DIRECTIONS_BOAT),
+    ("bike", ICON_PEDAL_BIKE),
+    ("electric-bike", ICON_ELECTRIC_BIKE),
+    ("parking", ICON_LOCAL_PARKING),
+    ("sun", ICON_WB_SUNNY),
+    ("cloud", ICON_CLOUD),
+    ("thermometer", ICON_THERMOSTAT),
+    ("air", ICON_AIR),
+    ("water", ICON_WATER_DROP),
+    ("waves", ICON_WAVES),
+    ("moon", ICON_DARK_MODE),
+    ("event", ICON_EVENT),
+    ("schedule", ICON_SCHEDULE),
+    ("info", ICON_INFO),
+    ("warning", ICON_WARNING),
+];
+
+/// Look up a configured icon; anything that isn't a known name is shown as-is,
+/// so a literal glyph works too.
+pub(crate) fn icon_glyph(name: &str) -> &str {
+    CARD_ICONS
+        .iter()
+        .find(|(icon, _)| icon.eq_ignore_ascii_case(name))
+        .map(|(_, glyph)| *glyph)
+        .unwrap_or(name)
+}
+
+/// Per-card presentation overrides, keyed by card key in the config.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub(crate) struct CardOverride {
+    /// Replaces the feed's query name or the card key
+    #[serde(default)]
+    pub(crate) title: Option<String>,
+    /// Extra line shown under the title
+    #[serde(default)]
+    pub(crate) subtitle: Option<String>,
+    /// Icon name from the card editor's list, or a literal glyph
+    #[serde(default)]
+    pub(crate) icon: Option<String>,
+    /// Replaces the theme's accent color on this card
+    #[serde(default, deserialize_with = "theme::rgb_format::option")]
+    pub(crate) accent: Option<[u8; 3]>,
+}
+
+impl CardOverride {
+    fn is_empty(&self) -> bool {
+        *self == CardOverride::default()
+    }
+}
+
+/// Edit an optional string, treating an empty field as unset.
+fn optional_text_row(ui: &mut egui::Ui, label: &str, value: &mut Option<String>, hint: &str) {
+    ui.label(label);
+    let mut text = value.clone().unwrap_or_default();
+    if ui
+        .add(egui::TextEdit::singleline(&mut text).hint_text(hint))
+        .changed()
+    {
+        *value = (!text.trim().is_empty()).then_some(text);
+    }
+    ui.end_row();
+}
+
+impl ApollosKiosk {
+    pub(crate) fn card_override(&self, key: &str) -> Option<&CardOverride> {
+        self.config.cards.get(key)
+    }
+
+    pub(crate) fn open_card_editor(&mut self, key: String) {
+        let draft = self.card_override(&key).cloned().unwrap_or_default();
+        self.card_editor = Some((key, draft));
+    }
+
+    pub(crate) fn render_card_editor(&mut self, ctx: &egui::Context) {
+        let Some((key, mut draft)) = self.card_editor.clone() else {
+            return;
+        };
+
+        let mut open = true;
+        let mut save = false;
+        let mut reset = false;
+        let mut cancel = false;
+        let default_title = self
+            .data
+            .get(&key)
+            .and_then(|entry| entry.query_info.as_ref())
+            .map(|q| q.name.clone())
+            .unwrap_or_else(|| key.clone());
+        let theme_accent = self.get_current_theme().accent_color;
+
+        egui::Window::new(format!("Edit Card: {}", key))
+            .open(&mut open)
+            .resizable(false)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("card_editor_grid")
+                    .num_columns(2)
+                    .spacing([16.0, 6.0])
+                    .show(ui, |ui| {
+                        optional_text_row(ui, "Title", &mut draft.title, &default_title);
+                        optional_text_row(ui, "Subtitle", &mut draft.subtitle, "");
+
+                        ui.label("Icon");
+                        let selected = draft.icon.as_deref().map(icon_glyph).unwrap_or("None");
+                        egui::ComboBox::from_id_salt("card_editor_icon")
+                            .selected_text(selected)
+                            .show_ui(ui, |ui| {
+                                if ui.selectable_label(draft.icon.is_none(), "None").clicked() {
+                                    draft.icon = None;
+                                }
+                                for (name, glyph) in CARD_ICONS {
+                                    let is_selected = draft.icon.as_deref() == Some(*name);
+                                    if ui
+                                        .selectable_label(
+                                            is_selected,
+                                            format!("{}  {}", glyph, name),
+                                        )
+                                        .clicked()
+                                    {
+                                        draft.icon = Some(name.to_string());
+                                    }
+                                }
+                            });
+                        ui.end_row();
+
+                        ui.label("Accent color");
+                        ui.horizontal(|ui| {
+                            let mut enabled = draft.accent.is_some();
+                            if ui.checkbox(&mut enabled, "").changed() {
+                                draft.accent = enabled.then_some(theme_accent);
+                            }
+                            match &mut draft.accent {
+                                Some(color) => {
+                                    egui::color_picker::color_edit_button_srgb(ui, color);
+                                }
+                                None => {
+                                    ui.label(egui::RichText::new("theme").weak());
+                                }
+                            }
+                        });
+                        ui.end_row();
+                    });
+
+                ui.add_space(8.0);
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("💾 Save").clicked() {
+                        save = true;
+                    }
+                    if ui.button("Reset").clicked() {
+                        reset = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        cancel = true;
+                    }
+                });
+            });
+
+        if save || reset {
+            if reset || draft.is_empty() {
+                self.config.cards.remove(&key);
+            } else {
+                self.config.cards.insert(key, draft);
+            }
+            self.save_config();
+            self.card_editor = None;
+        } else if cancel || !open {
+            self.card_editor = None;
+        } else {
+            self.card_editor = Some((key, draft));
+        }
+    }
+}
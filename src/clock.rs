@@ -0,0 +1,167 @@
+use crate::ApollosKiosk;
+use crate::logging::CONFIG;
+use chrono::{DateTime, Local, TimeZone, Timelike, Utc};
+use eframe::egui;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::{Display, Write};
+use std::time::Duration;
+
+/// Key prefix for local clock cards; these never arrive over MQTT.
+pub(crate) const CLOCK_KEY_PREFIX: &str = "clock-";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct ClockConfig {
+    /// Card title, defaults to "Clock"
+    #[serde(default)]
+    pub(crate) title: Option<String>,
+    /// chrono format string for the main time
+    #[serde(default = "default_time_format")]
+    pub(crate) time_format: String,
+    /// chrono format string for the date line; no date is shown if empty
+    #[serde(default = "default_date_format")]
+    pub(crate) date_format: String,
+    /// Secondary time zones shown beneath the main clock
+    #[serde(default)]
+    pub(crate) zones: Vec<ClockZone>,
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        ClockConfig {
+            title: None,
+            time_format: default_time_format(),
+            date_format: default_date_format(),
+            zones: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct ClockZone {
+    /// IANA time zone name, e.g. "Europe/London"
+    pub(crate) tz: String,
+    /// Label shown next to the time, defaults to the zone name
+    #[serde(default)]
+    pub(crate) label: Option<String>,
+}
+
+fn default_time_format() -> String {
+    "%H:%M".to_string()
+}
+
+fn default_date_format() -> String {
+    "%A, %B %-d".to_string()
+}
+
+pub(crate) fn default_clocks() -> HashMap<String, ClockConfig> {
+    HashMap::from([(format!("{}local", CLOCK_KEY_PREFIX), ClockConfig::default())])
+}
+
+/// Format a time with a user-supplied chrono format string.
+///
+/// Unlike `format(..).to_string()` this doesn't panic on an invalid format.
+fn format_time<Tz: TimeZone>(time: &DateTime<Tz>, format: &str) -> String
+where
+    Tz::Offset: Display,
+{
+    let mut out = String::new();
+    match write!(out, "{}", time.format(format)) {
+        Ok(()) => out,
+        Err(_) => "Invalid format".to_string(),
+    }
+}
+
+/// Whether a chrono format string includes seconds.
+fn shows_seconds(format: &str) -> bool {
+    ["%S", "%T", "%X", "%r", "%s"]
+        .iter()
+        .any(|spec| format.contains(spec))
+}
+
+impl ApollosKiosk {
+    /// Put configured clocks that aren't placed anywhere into the unassigned tray.
+    pub(crate) fn register_local_cards(&mut self) {
+        let mut keys: Vec<_> = self.config.clocks.keys().cloned().collect();
+        keys.sort();
+
+        for key in keys {
+            let is_assigned = self.config.panels.iter().any(|p| p.contains(&key))
+                || self.config.unassigned.contains(&key);
+            if !is_assigned {
+                info!(target: CONFIG, "Adding local card {} to unassigned", key);
+                self.config.unassigned.push(key);
+            }
+        }
+    }
+
+    pub(crate) fn render_clock_card(
+        &self,
+        ui: &mut egui::Ui,
+        clock: &ClockConfig,
+        scale: f32,
+        accent_color: egui::Color32,
+    ) {
+        let theme = self.get_current_theme();
+        let now = Local::now();
+
+        ui.vertical_centered(|ui| {
+            ui.label(
+                egui::RichText::new(format_time(&now, &clock.time_format))
+                    .size(120.0 * scale)
+                    .strong()
+                    .color(accent_color),
+            );
+            if !clock.date_format.is_empty() {
+                ui.label(
+                    egui::RichText::new(format_time(&now, &clock.date_format)).size(32.0 * scale),
+                );
+            }
+        });
+
+        if !clock.zones.is_empty() {
+            ui.add_space(12.0 * scale);
+            let utc = Utc::now();
+
+            for zone in &clock.zones {
+                let label = zone.label.as_deref().unwrap_or(&zone.tz);
+                let time = match zone.tz.parse::<chrono_tz::Tz>() {
+                    Ok(tz) => format_time(&utc.with_timezone(&tz), &clock.time_format),
+                    Err(_) => "Unknown zone".to_string(),
+                };
+
+                egui::Frame::NONE
+                    .fill(ui.visuals().extreme_bg_color)
+                    .corner_radius(theme.inner_corner_radius() * scale)
+                    .inner_margin(12.0 * scale)
+                    .show(ui, |ui| {
+                        ui.set_width(ui.available_width());
+                        ui.horizontal(|ui| {
+                            ui.label(egui::RichText::new(label).size(24.0 * scale));
+                            ui.with_layout(
+                                egui::Layout::right_to_left(egui::Align::Center),
+                                |ui| {
+                                    ui.label(
+                                        egui::RichText::new(time)
+                                            .monospace()
+                                            .strong()
+                                            .size(28.0 * scale),
+                                    );
+                                },
+                            );
+                        });
+                    });
+                ui.add_space(8.0 * scale);
+            }
+        }
+
+        // Tick every second, or at the start of each minute if seconds aren't shown
+        let delay = if shows_seconds(&clock.time_format) {
+            Duration::from_millis(1000 - u64::from(now.timestamp_subsec_millis().min(999)))
+        } else {
+            Duration::from_secs(60 - u64::from(now.second()))
+        };
+        ui.ctx().request_repaint_after(delay);
+    }
+}
@@ -0,0 +1,480 @@
+use crate::logging::CONFIG;
+use crate::{ApollosKiosk, Config, fonts, secrets};
+use eframe::egui;
+use log::{info, warn};
+use std::fs::{self, File};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, SystemTime};
+
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Schema version written to the config; older files are migrated when loaded.
+pub(crate) const CONFIG_VERSION: u32 = 1;
+
+/// Each migration upgrades a config from version `index` to `index + 1`.
+const MIGRATIONS: &[fn(&mut toml::Table)] = &[migrate_v0_to_v1];
+
+/// Unversioned configs may lack the layout or have a different number of panels.
+fn migrate_v0_to_v1(table: &mut toml::Table) {
+    let mut orphaned = vec![];
+    match table.get_mut("panels") {
+        Some(toml::Value::Array(panels)) => {
+            // Cards on panels that no longer exist go back to the tray
+            for panel in panels.drain(3.min(panels.len())..) {
+                if let toml::Value::Array(keys) = panel {
+                    orphaned.extend(keys);
+                }
+            }
+            panels.resize(3, toml::Value::Array(vec![]));
+        }
+        _ => {
+            table.insert(
+                "panels".to_string(),
+                toml::Value::Array(vec![toml::Value::Array(vec![]); 3]),
+            );
+        }
+    }
+
+    if let toml::Value::Array(unassigned) = table
+        .entry("unassigned")
+        .or_insert_with(|| toml::Value::Array(vec![]))
+    {
+        unassigned.extend(orphaned);
+    }
+}
+
+/// Turn a TOML parse error into a one-line message with the line number.
+fn describe_parse_error(content: &str, e: &toml::de::Error) -> String {
+    let message = e
+        .message()
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ");
+    match e.span() {
+        Some(span) => {
+            let line = content[..span.start.min(content.len())]
+                .matches('\n')
+                .count()
+                + 1;
+            format!("line {}: {}", line, message)
+        }
+        None => message,
+    }
+}
+
+/// Read the config file, migrating older versions and using the defaults if it
+/// doesn't exist yet.
+pub(crate) fn load_config(path: &Path) -> Result<Config, String> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Config::default()),
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let mut table: toml::Table =
+        toml::from_str(&content).map_err(|e| describe_parse_error(&content, &e))?;
+    let version = table
+        .get("version")
+        .and_then(|v| v.as_integer())
+        .unwrap_or(0)
+        .max(0) as usize;
+
+    if version > CONFIG_VERSION as usize {
+        return Err(format!(
+            "version {} is newer than this kiosk supports ({})",
+            version, CONFIG_VERSION
+        ));
+    }
+    if version == CONFIG_VERSION as usize {
+        // Parse the text directly so errors keep their line numbers
+        return toml::from_str(&content).map_err(|e| describe_parse_error(&content, &e));
+    }
+
+    for migration in &MIGRATIONS[version..] {
+        migration(&mut table);
+    }
+    table.insert(
+        "version".to_string(),
+        toml::Value::Integer(CONFIG_VERSION.into()),
+    );
+    info!(
+        target: CONFIG,
+        "Migrated {} from version {} to {}",
+        path.display(),
+        version,
+        CONFIG_VERSION
+    );
+    toml::Value::Table(table)
+        .try_into()
+        .map_err(|e: toml::de::Error| e.message().trim().to_string())
+}
+
+/// A file next to the config named after all of it, e.g. `kiosk.conf.1` for
+/// `kiosk.conf`, so configs differing only in extension don't share files.
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", suffix));
+    PathBuf::from(name)
+}
+
+pub(crate) fn backup_path(path: &Path, n: usize) -> PathBuf {
+    sibling_path(path, &n.to_string())
+}
+
+fn broken_path(path: &Path) -> PathBuf {
+    sibling_path(path, "broken")
+}
+
+/// Shift `<config>.1..N` along by one and copy the current file to `<config>.1`,
+/// e.g. `config.toml.1`.
+fn rotate_backups(path: &Path, count: usize) -> std::io::Result<()> {
+    if count == 0 || !path.exists() {
+        return Ok(());
+    }
+
+    let _ = fs::remove_file(backup_path(path, count));
+    for n in (1..count).rev() {
+        let from = backup_path(path, n);
+        if from.exists() {
+            fs::rename(&from, backup_path(path, n + 1))?;
+        }
+    }
+    fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
+
+/// Write the config so that a crash leaves either the old or the new file, never
+/// a partial one, keeping the previous `backups` versions alongside it.
+pub(crate) fn write_config(path: &Path, config: &Config, backups: usize) -> Result<(), String> {
+    let content = toml::to_string_pretty(config).map_err(|e| e.to_string())?;
+    // Compare parsed tables, since maps don't serialize in a stable order and
+    // rewriting an unchanged config would rotate the backups away
+    let unchanged = fs::read_to_string(path).is_ok_and(|existing| {
+        existing == content
+            || toml::from_str::<toml::Table>(&existing).ok()
+                == toml::from_str::<toml::Table>(&content).ok()
+    });
+    if unchanged {
+        return Ok(());
+    }
+
+    let tmp_path = sibling_path(path, "tmp");
+    let write_tmp = || -> std::io::Result<()> {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()
+    };
+    write_tmp().map_err(|e| format!("{}: {}", tmp_path.display(), e))?;
+
+    rotate_backups(path, backups).map_err(|e| format!("backing up: {}", e))?;
+    fs::rename(&tmp_path, path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    // Make the rename itself durable
+    if let Some(dir) = path.parent()
+        && let Ok(dir) = File::open(dir)
+    {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+fn file_stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Poll the config file and send the result of re-reading it whenever it changes.
+pub(crate) fn spawn_config_watcher(
+    path: PathBuf,
+    ctx: egui::Context,
+) -> Receiver<Result<Config, String>> {
+    let (tx, rx) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        let mut last_stamp = file_stamp(&path);
+        loop {
+            std::thread::sleep(CONFIG_POLL_INTERVAL);
+
+            let stamp = file_stamp(&path);
+            if stamp == last_stamp {
+                continue;
+            }
+            last_stamp = stamp;
+
+            // A deleted config shouldn't wipe the layout; it's rewritten on the next save
+            if stamp.is_none() {
+                continue;
+            }
+
+            if tx.send(load_config(&path)).is_err() {
+                break;
+            }
+            ctx.request_repaint();
+        }
+    });
+
+    rx
+}
+
+impl ApollosKiosk {
+    /// Put feeds that have already arrived but aren't in the layout in the tray.
+    pub(crate) fn track_unplaced_feeds(&mut self) {
+        let mut keys: Vec<_> = self.data.keys().cloned().collect();
+        keys.sort();
+        for key in keys {
+            let is_assigned = self.config.panels.iter().any(|p| p.contains(&key))
+                || self.config.unassigned.contains(&key);
+            if !is_assigned {
+                self.config.unassigned.push(key);
+            }
+        }
+    }
+
+    /// The config as it should be written to disk.
+    pub(crate) fn config_snapshot(&self) -> Config {
+        let mut cfg = self.config.clone();
+        cfg.current_theme = self.current_theme.clone();
+        cfg.version = CONFIG_VERSION;
+        cfg
+    }
+
+    /// Apply a config re-read from disk, keeping pushed settings and command line overrides.
+    pub(crate) fn reload_config(&mut self, ctx: &egui::Context, loaded: Result<Config, String>) {
+        let mut config = match loaded {
+            Ok(config) => config,
+            Err(e) => {
+                warn!(
+                    target: CONFIG,
+                    "Failed to reload {}: {}",
+                    self.config_path.display(),
+                    e
+                );
+                self.config_error = Some(e);
+                return;
+            }
+        };
+        self.config_error = None;
+        // A password or PIN written into the file is moved out and the file rewritten
+        let scrub_password = config.has_secrets();
+        secrets::migrate_config_password(&config, &self.secrets_dir);
+
+        let changed = if self.pushed_config.is_empty() {
+            self.apply_config(ctx, config)
+        } else {
+            self.pushed_config.track_local_edits(&mut config);
+            match self.pushed_config.layer(&config) {
+                Ok(layered) => self.apply_config(ctx, layered),
+                Err(e) => {
+                    warn!(target: CONFIG, "Failed to apply pushed config: {}", e);
+                    false
+                }
+            }
+        };
+        if changed {
+            info!(target: CONFIG, "Reloaded {}", self.config_path.display());
+        }
+
+        if scrub_password {
+            self.scrub_config_password();
+        } else if changed && !self.pushed_config.is_empty() {
+            // Keep the file in line with the pushed settings that won
+            self.save_config();
+        }
+    }
+
+    /// Switch to a new config, keeping command line overrides. Returns whether
+    /// anything changed.
+    pub(crate) fn apply_config(&mut self, ctx: &egui::Context, config: Config) -> bool {
+        let mut config = config;
+        config.apply_args(&self.args);
+        config.keep_secrets(&self.config);
+        config.require_pin_to_lock();
+
+        // Our own saves come back through the watcher unchanged
+        let unchanged = toml::Value::try_from(&config).ok()
+            == toml::Value::try_from(self.config_snapshot()).ok();
+        if unchanged {
+            return false;
+        }
+
+        let old = std::mem::replace(&mut self.config, config);
+        self.current_theme = self.config.current_theme.clone();
+        self.register_local_cards();
+        self.track_unplaced_feeds();
+
+        if old.fonts != self.config.fonts {
+            fonts::install_fonts(ctx, &self.config.fonts);
+        }
+        let data_settings = |c: &Config| {
+            (
+                c.mqtt_host.clone(),
+                c.mqtt_username.clone(),
+                c.mqtt_password.clone(),
+                c.mqtt_topic.clone(),
+            )
+        };
+        if data_settings(&old) != data_settings(&self.config) {
+            self.restart_data_connection(ctx);
+        }
+        let sync_settings = |c: &Config| {
+            (
+                c.mqtt_theme_sync,
+                c.mqtt_theme_host.clone(),
+                c.mqtt_theme_username.clone(),
+                c.mqtt_theme_password.clone(),
+                c.mqtt_theme_topic.clone(),
+            )
+        };
+        if sync_settings(&old) != sync_settings(&self.config) {
+            self.restart_theme_sync(ctx);
+        }
+        self.apply_theme(ctx);
+        true
+    }
+
+    /// Move the broken config file aside and save the current settings in its place.
+    fn overwrite_broken_config(&mut self) {
+        let broken_path = broken_path(&self.config_path);
+        match fs::rename(&self.config_path, &broken_path) {
+            Ok(()) => info!(target: CONFIG, "Moved broken config to {}", broken_path.display()),
+            Err(e) => {
+                warn!(target: CONFIG, "Failed to move broken config aside: {}", e);
+                return;
+            }
+        }
+        self.config_error = None;
+        self.save_config();
+    }
+
+    /// Strip above the layout explaining why the config file isn't being used
+    /// or couldn't be saved.
+    pub(crate) fn render_config_error(&mut self, ctx: &egui::Context) {
+        let message = match (&self.config_error, &self.save_error) {
+            (Some(error), _) => format!(
+                "⚠ {} has an error ({}). Changes won't be saved until it is fixed.",
+                self.config_path.display(),
+                error
+            ),
+            (None, Some(error)) => format!("⚠ Failed to save settings: {}", error),
+            (None, None) => return,
+        };
+
+        let mut overwrite = false;
+        egui::TopBottomPanel::top("config_error")
+            .frame(
+                egui::Frame::NONE
+                    .fill(egui::Color32::from_rgb(120, 30, 30))
+                    .inner_margin(egui::Margin::symmetric(16, 8)),
+            )
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new(message).color(egui::Color32::WHITE));
+                    if self.config_error.is_some() && !self.config.locked {
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            overwrite = ui
+                                .button("Replace with current settings")
+                                .on_hover_text(format!(
+                                    "The broken file is kept as {}",
+                                    broken_path(&self.config_path).display()
+                                ))
+                                .clicked();
+                        });
+                    }
+                });
+            });
+
+        if overwrite {
+            self.overwrite_broken_config();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_config(name: &str, content: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("apollos-kiosk-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("{}.toml", name));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn parse_error(content: &str) -> String {
+        let e = toml::from_str::<toml::Table>(content).unwrap_err();
+        describe_parse_error(content, &e)
+    }
+
+    #[test]
+    fn parse_errors_name_the_line() {
+        let error = parse_error("version = 1\n\nlocked = yes\n");
+        assert!(error.starts_with("line 3: "), "{}", error);
+        assert!(!error.contains('\n'), "{}", error);
+    }
+
+    #[test]
+    fn parse_errors_on_the_first_line() {
+        assert!(parse_error("= 1").starts_with("line 1: "));
+    }
+
+    #[test]
+    fn v0_cards_on_extra_panels_go_back_to_the_tray() {
+        let mut table: toml::Table =
+            toml::from_str("panels = [[\"a\"], [], [\"b\"], [\"c\", \"d\"]]\nunassigned = [\"e\"]")
+                .unwrap();
+        migrate_v0_to_v1(&mut table);
+        let config: Config = toml::Value::Table(table).try_into().unwrap();
+        assert_eq!(config.panels, [vec!["a"], vec![], vec!["b"]]);
+        assert_eq!(config.unassigned, ["e", "c", "d"]);
+    }
+
+    #[test]
+    fn v0_without_a_layout_gets_empty_panels() {
+        let mut table: toml::Table = toml::from_str("panels = [[\"a\"]]").unwrap();
+        migrate_v0_to_v1(&mut table);
+        let config: Config = toml::Value::Table(table).try_into().unwrap();
+        assert_eq!(config.panels, [vec!["a"], vec![], vec![]]);
+        assert!(config.unassigned.is_empty());
+
+        let mut table = toml::Table::new();
+        migrate_v0_to_v1(&mut table);
+        let config: Config = toml::Value::Table(table).try_into().unwrap();
+        assert_eq!(config.panels, [vec![], vec![], vec![]] as [Vec<String>; 3]);
+    }
+
+    #[test]
+    fn loading_an_unversioned_config_migrates_it() {
+        let path = temp_config("unversioned", "current_theme = \"Light\"\n");
+        let config = load_config(&path).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.current_theme, "Light");
+        assert_eq!(config.panels, [vec![], vec![], vec![]] as [Vec<String>; 3]);
+    }
+
+    #[test]
+    fn loading_a_newer_config_fails() {
+        let path = temp_config(
+            "newer",
+            &format!(
+                "version = {}\npanels = [[], [], []]\nunassigned = []\n",
+                CONFIG_VERSION + 1
+            ),
+        );
+        let Err(error) = load_config(&path) else {
+            panic!("loaded a config from a newer version");
+        };
+        assert!(error.contains("newer"), "{}", error);
+    }
+
+    #[test]
+    fn loading_a_missing_config_uses_the_defaults() {
+        let path = std::env::temp_dir().join("apollos-kiosk-missing-config.toml");
+        let config = load_config(&path).unwrap();
+        assert_eq!(config.current_theme, Config::default().current_theme);
+    }
+}
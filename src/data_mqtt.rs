@@ -0,0 +1,275 @@
+use crate::logging::MQTT_DATA;
+use crate::theme_sync::{self, SyncStatus, retry_with_backoff};
+use crate::{ApollosKiosk, Args, Config, profile};
+use eframe::egui;
+use log::{debug, error, info, warn};
+use paho_mqtt as mqtt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Broker URI for a configured host: a bare host name uses plain MQTT on port
+/// 1883, anything with a scheme ("ssl://broker:8883") is used as given.
+pub(crate) fn broker_uri(host: &str) -> String {
+    if host.contains("://") {
+        host.to_string()
+    } else {
+        format!("tcp://{}:1883", host)
+    }
+}
+
+/// Connection to the data broker, forwarding every message to the kiosk.
+///
+/// Dropping it tells the receiving thread to disconnect and stop; the UI
+/// thread never waits on the broker.
+pub(crate) struct DataConnection {
+    client: mqtt::Client,
+    error: Arc<Mutex<Option<String>>>,
+    connected_once: Arc<Mutex<bool>>,
+    stop: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+}
+
+fn disconnect(cli: &mqtt::Client) {
+    if cli.is_connected()
+        && let Err(e) = cli.disconnect(None)
+    {
+        warn!(target: MQTT_DATA, "Failed to disconnect: {:?}", e);
+    }
+}
+
+impl DataConnection {
+    /// Connect and subscribe in a background thread. Returns `None` if no data
+    /// topic is configured yet.
+    ///
+    /// `previous` is the thread of the connection being replaced. It uses the
+    /// same client id, so the new one only connects once it has finished.
+    pub(crate) fn start(
+        config: &Config,
+        args: &Args,
+        tx: Sender<mqtt::Message>,
+        ctx: egui::Context,
+        previous: Option<JoinHandle<()>>,
+    ) -> Option<Self> {
+        let topic = config.mqtt_topic.clone()?;
+        let username = config.mqtt_username.clone();
+        let password = config.mqtt_password.clone();
+
+        // Anonymous clients still need an id no other kiosk uses
+        let client_id = match &username {
+            Some(username) => profile::mqtt_client_id(username, args.instance_name().as_deref()),
+            None => format!("{}-data", theme_sync::sync_source_id()),
+        };
+        let create_opts = mqtt::CreateOptionsBuilder::new()
+            .server_uri(broker_uri(&config.mqtt_host))
+            .client_id(client_id)
+            .finalize();
+
+        let cli = match mqtt::Client::new(create_opts) {
+            Ok(cli) => cli,
+            Err(e) => {
+                error!(target: MQTT_DATA, "Failed to create client: {:?}", e);
+                return None;
+            }
+        };
+
+        // Commands, alerts, layouts and pushed config share the data connection
+        let extra_topics: Vec<(&str, String)> = [
+            ("commands", &args.mqtt_command_topic),
+            ("alerts", &args.mqtt_alert_topic),
+            ("layouts", &args.mqtt_layout_topic),
+            // Fleet first, so a retained kiosk config is layered over it
+            ("config", &args.mqtt_fleet_config_topic),
+            ("config", &args.mqtt_kiosk_config_topic),
+        ]
+        .into_iter()
+        .filter_map(|(kind, topic)| Some((kind, topic.clone()?)))
+        .collect();
+
+        let error = Arc::new(Mutex::new(None));
+        let connected_once = Arc::new(Mutex::new(false));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread_cli = cli.clone();
+        let thread_error = error.clone();
+        let thread_connected_once = connected_once.clone();
+        let thread_stop = stop.clone();
+
+        let worker = std::thread::spawn(move || {
+            let cli = thread_cli;
+            if let Some(previous) = previous {
+                let _ = previous.join();
+            }
+            // Consume before checking the stop flag, so a stop after the check
+            // always wakes the loop below
+            let rx_mqtt = cli.start_consuming();
+            let stopped = || thread_stop.load(Ordering::SeqCst);
+            let fail = |message: String| {
+                *thread_error.lock().unwrap() = Some(message);
+                ctx.request_repaint();
+            };
+
+            let mut conn_opts = mqtt::ConnectOptionsBuilder::new();
+            conn_opts
+                .keep_alive_interval(Duration::from_secs(20))
+                .connect_timeout(Duration::from_secs(10))
+                .clean_session(true)
+                .automatic_reconnect(Duration::from_secs(1), Duration::from_secs(30));
+            if let Some(username) = &username {
+                conn_opts.user_name(username);
+            }
+            if let Some(password) = &password {
+                conn_opts.password(password);
+            }
+
+            let conn_opts = conn_opts.finalize();
+            let connected = retry_with_backoff(
+                || {
+                    // Dropped while connecting: leave before taking messages for it
+                    if stopped() {
+                        return Ok(());
+                    }
+                    if !cli.is_connected() {
+                        cli.connect(conn_opts.clone()).map_err(|e| e.to_string())?;
+                    }
+                    cli.subscribe(&topic, 1)
+                        .map(|_| ())
+                        .map_err(|e| format!("subscribe to '{}': {}", topic, e))
+                },
+                |e, retry| {
+                    error!(
+                        target: MQTT_DATA,
+                        "Failed to connect, retrying in {}s: {}",
+                        retry.as_secs(),
+                        e
+                    );
+                    fail(format!("{} (retrying)", e));
+                },
+                |retry| {
+                    // Stopping wakes this through the consumer channel
+                    let deadline = Instant::now() + retry;
+                    while let Some(wait) = deadline.checked_duration_since(Instant::now()) {
+                        if stopped() {
+                            return false;
+                        }
+                        match rx_mqtt.recv_timeout(wait) {
+                            Err(RecvTimeoutError::Timeout) => break,
+                            Err(RecvTimeoutError::Disconnected) => return false,
+                            Ok(_) => {}
+                        }
+                    }
+                    !stopped()
+                },
+            );
+            if !connected || stopped() {
+                disconnect(&cli);
+                info!(target: MQTT_DATA, "Stopped");
+                return;
+            }
+            *thread_error.lock().unwrap() = None;
+            info!(target: MQTT_DATA, "Connected and subscribed to {}", topic);
+            *thread_connected_once.lock().unwrap() = true;
+            ctx.request_repaint();
+
+            for (kind, extra_topic) in &extra_topics {
+                match cli.subscribe(extra_topic, 1) {
+                    Ok(_) => info!(target: MQTT_DATA, "Subscribed to {} on {}", kind, extra_topic),
+                    Err(e) => error!(
+                        target: MQTT_DATA,
+                        "Failed to subscribe to {} topic '{}': {:?}",
+                        kind,
+                        extra_topic,
+                        e
+                    ),
+                }
+            }
+
+            for msg in rx_mqtt.iter() {
+                if let Some(msg) = msg {
+                    debug!(target: MQTT_DATA, "Received message on '{}'", msg.topic());
+                    let _ = tx.send(msg);
+                    ctx.request_repaint();
+                } else if stopped() {
+                    break;
+                } else {
+                    // None indicates a disconnection, but with auto-reconnect enabled
+                    // the client will handle reconnection automatically
+                    warn!(target: MQTT_DATA, "Disconnected, waiting for reconnection...");
+                    ctx.request_repaint();
+                }
+            }
+            disconnect(&cli);
+            info!(target: MQTT_DATA, "Stopped");
+        });
+
+        Some(DataConnection {
+            client: cli,
+            error,
+            connected_once,
+            stop,
+            worker: Some(worker),
+        })
+    }
+
+    /// Ask the receiving thread to disconnect, returning it so a replacement
+    /// can wait for it.
+    fn shut_down(&mut self) -> Option<JoinHandle<()>> {
+        self.stop.store(true, Ordering::SeqCst);
+        // Wakes the thread if it is already waiting for messages
+        self.client.stop_consuming();
+        self.worker.take()
+    }
+
+    pub(crate) fn status(&self) -> SyncStatus {
+        if let Some(e) = self.error.lock().unwrap().clone() {
+            SyncStatus::Failed(e)
+        } else if self.client.is_connected() {
+            SyncStatus::Connected
+        } else if *self.connected_once.lock().unwrap() {
+            SyncStatus::Reconnecting
+        } else {
+            SyncStatus::Connecting
+        }
+    }
+}
+
+impl Drop for DataConnection {
+    fn drop(&mut self) {
+        self.shut_down();
+    }
+}
+
+impl ApollosKiosk {
+    /// Drop the data connection and connect again with the current settings.
+    pub(crate) fn restart_data_connection(&mut self, ctx: &egui::Context) {
+        let previous = self
+            .data_connection
+            .take()
+            .and_then(|mut connection| connection.shut_down());
+        self.data_connection = DataConnection::start(
+            &self.config,
+            &self.args,
+            self.data_tx.clone(),
+            ctx.clone(),
+            previous,
+        );
+    }
+
+    /// Show the data connection state in the bottom bar while it isn't connected.
+    pub(crate) fn show_data_connection_status(&self, ui: &mut egui::Ui) {
+        match &self.data_connection {
+            Some(connection) => {
+                let status = connection.status();
+                if status != SyncStatus::Connected {
+                    status.show(ui);
+                    ui.ctx().request_repaint_after(Duration::from_secs(1));
+                }
+            }
+            None => {
+                ui.label(egui::RichText::new("Data MQTT not configured").weak());
+            }
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+
+/// Quote a `.env` value unless it is plain enough to need none.
+fn env_value(value: &str) -> String {
+    let plain = value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "/_-.:".contains(c));
+    if plain && !value.is_empty() {
+        value.to_string()
+    } else {
+        let escaped = value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('$', "\\$");
+        format!("\"{}\"", escaped)
+    }
+}
+
+/// Names of the variables a `.env` file sets.
+pub(crate) fn read_vars(path: &Path) -> HashSet<String> {
+    dotenvy::from_path_iter(path)
+        .map(|vars| vars.filter_map(Result::ok).map(|(name, _)| name).collect())
+        .unwrap_or_default()
+}
+
+/// Set or remove variables in a `.env` file, keeping its other lines.
+///
+/// The file is replaced atomically like the config, since it may hold
+/// hand-written secrets that a crash mid-write would otherwise lose.
+pub(crate) fn write_env_file(path: &Path, vars: &[(&str, Option<&str>)]) -> Result<(), String> {
+    let existing = match fs::read_to_string(path) {
+        Ok(content) => content,
+        // Nothing to set and no file to clean up
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            if vars.iter().all(|(_, value)| value.is_none()) {
+                return Ok(());
+            }
+            String::new()
+        }
+        Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    };
+
+    let is_var = |line: &str, key: &str| {
+        let line = line.trim_start();
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        line.split_once('=')
+            .is_some_and(|(name, _)| name.trim() == key)
+    };
+
+    let mut lines: Vec<String> = existing
+        .lines()
+        .filter(|line| !vars.iter().any(|(key, _)| is_var(line, key)))
+        .map(str::to_string)
+        .collect();
+    for (key, value) in vars {
+        if let Some(value) = value {
+            lines.push(format!("{}={}", key, env_value(value)));
+        }
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let content = lines.join("\n") + "\n";
+    if content == existing {
+        return Ok(());
+    }
+
+    let tmp_path = path.with_extension("env.tmp");
+    let write_tmp = || -> std::io::Result<()> {
+        let mut file = File::create(&tmp_path)?;
+        // Keep the permissions of a file that may hold passwords
+        if let Ok(metadata) = fs::metadata(path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.write_all(content.as_bytes())?;
+        file.sync_all()
+    };
+    write_tmp().map_err(|e| format!("{}: {}", tmp_path.display(), e))?;
+    fs::rename(&tmp_path, path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    if let Some(dir) = path.parent()
+        && let Ok(dir) = File::open(dir)
+    {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_env_file(name: &str, content: Option<&str>) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("apollos-kiosk-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("{}.env", name));
+        match content {
+            Some(content) => fs::write(&path, content).unwrap(),
+            None => {
+                let _ = fs::remove_file(&path);
+            }
+        }
+        path
+    }
+
+    fn read_back(path: &Path) -> Vec<(String, String)> {
+        dotenvy::from_path_iter(path)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    #[test]
+    fn env_value_quotes_only_when_needed() {
+        assert_eq!(
+            env_value("tcp://broker.local:1883"),
+            "tcp://broker.local:1883"
+        );
+        assert_eq!(env_value("kiosk/alerts"), "kiosk/alerts");
+        assert_eq!(env_value(""), "\"\"");
+        assert_eq!(env_value("two words"), "\"two words\"");
+        assert_eq!(env_value(r#"a"b\c$d"#), r#""a\"b\\c\$d""#);
+    }
+
+    #[test]
+    fn written_values_read_back_unchanged() {
+        let path = temp_env_file("quoting", None);
+        let value = r#"p@ss "word" $HOME \n#x"#;
+        write_env_file(
+            &path,
+            &[("MQTT_PASSWORD", Some(value)), ("EMPTY", Some(""))],
+        )
+        .unwrap();
+
+        assert_eq!(
+            read_back(&path),
+            vec![
+                ("MQTT_PASSWORD".to_string(), value.to_string()),
+                ("EMPTY".to_string(), String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn replaces_export_lines_and_keeps_the_rest() {
+        let path = temp_env_file(
+            "export",
+            Some("# kiosk\nexport MQTT_HOST=old\nMQTT_PASSWORD=secret\n"),
+        );
+        write_env_file(&path, &[("MQTT_HOST", Some("new"))]).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# kiosk\nMQTT_PASSWORD=secret\nMQTT_HOST=new\n"
+        );
+    }
+
+    #[test]
+    fn removes_unset_variables() {
+        let path = temp_env_file("remove", Some("MQTT_USERNAME=kiosk\nMQTT_TOPIC=data\n"));
+        write_env_file(&path, &[("MQTT_USERNAME", None)]).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "MQTT_TOPIC=data\n");
+    }
+
+    #[test]
+    fn removing_from_a_missing_file_creates_nothing() {
+        let path = temp_env_file("missing", None);
+        write_env_file(&path, &[("MQTT_USERNAME", None)]).unwrap();
+
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,153 @@
+use crate::logging::DISPLAY;
+use eframe::egui;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+
+/// High-legibility font shipped with the kiosk. It also covers symbols such as
+/// arrows and ⚡ that egui's default fonts lack.
+const BUNDLED_FONT: &[u8] = include_bytes!("../assets/fonts/DejaVuSans.ttf");
+const BUNDLED_FONT_NAME: &str = "bundled";
+
+/// Text size presets, applied on top of density and per-card text size.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum FontSize {
+    Small,
+    #[default]
+    Normal,
+    Large,
+    Huge,
+}
+
+impl FontSize {
+    pub(crate) const ALL: [FontSize; 4] = [
+        FontSize::Small,
+        FontSize::Normal,
+        FontSize::Large,
+        FontSize::Huge,
+    ];
+
+    /// Multiplier applied to card text and egui's text styles.
+    pub(crate) fn factor(self) -> f32 {
+        match self {
+            FontSize::Small => 0.85,
+            FontSize::Normal => 1.0,
+            FontSize::Large => 1.2,
+            FontSize::Huge => 1.45,
+        }
+    }
+
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            FontSize::Small => "Small",
+            FontSize::Normal => "Normal",
+            FontSize::Large => "Large",
+            FontSize::Huge => "Huge",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub(crate) struct FontConfig {
+    /// Font for regular text: "bundled" for the built-in font, or a .ttf/.otf path
+    #[serde(default)]
+    pub(crate) proportional: Option<String>,
+    /// Font file for monospace text such as departure times
+    #[serde(default)]
+    pub(crate) monospace: Option<String>,
+    /// Extra font files by family name, for use as a theme's `font_family`
+    #[serde(default)]
+    pub(crate) families: HashMap<String, String>,
+    #[serde(default)]
+    pub(crate) size: FontSize,
+}
+
+/// Read a configured font, where "bundled" means the font shipped with the kiosk.
+fn load_font(spec: &str) -> Result<(String, egui::FontData), String> {
+    if spec.eq_ignore_ascii_case(BUNDLED_FONT_NAME) {
+        return Ok((
+            BUNDLED_FONT_NAME.to_string(),
+            egui::FontData::from_static(BUNDLED_FONT),
+        ));
+    }
+
+    let path = Path::new(spec);
+    let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    // egui panics on data that isn't a font, so check it first
+    ab_glyph::FontRef::try_from_slice(&bytes)
+        .map_err(|e| format!("{}: not a usable font ({})", path.display(), e))?;
+    Ok((spec.to_string(), egui::FontData::from_owned(bytes)))
+}
+
+/// Install the configured fonts along with the bundled fallback and material icons.
+///
+/// Fonts that fail to load are replaced by the bundled font.
+pub(crate) fn install_fonts(ctx: &egui::Context, config: &FontConfig) {
+    let mut fonts = egui::FontDefinitions::default();
+
+    let add_font = |fonts: &mut egui::FontDefinitions, spec: &str| match load_font(spec) {
+        Ok((name, data)) => {
+            fonts.font_data.insert(name.clone(), Arc::new(data));
+            name
+        }
+        Err(e) => {
+            warn!(target: DISPLAY, "Failed to load {}, using the bundled font", e);
+            BUNDLED_FONT_NAME.to_string()
+        }
+    };
+
+    for (family, spec) in [
+        (egui::FontFamily::Proportional, &config.proportional),
+        (egui::FontFamily::Monospace, &config.monospace),
+    ] {
+        if let Some(spec) = spec {
+            let name = add_font(&mut fonts, spec);
+            info!(target: DISPLAY, "Using {} for {:?} text", name, family);
+            fonts.families.entry(family).or_default().insert(0, name);
+        }
+    }
+
+    // Fall back to the bundled font for glyphs the primary fonts don't have
+    fonts.font_data.insert(
+        BUNDLED_FONT_NAME.to_string(),
+        Arc::new(egui::FontData::from_static(BUNDLED_FONT)),
+    );
+    let icons = egui_material_icons::font_insert();
+    fonts
+        .font_data
+        .insert(icons.name.clone(), Arc::new(icons.data));
+    for list in fonts.families.values_mut() {
+        for name in [BUNDLED_FONT_NAME, icons.name.as_str()] {
+            if !list.iter().any(|n| n == name) {
+                list.push(name.to_string());
+            }
+        }
+    }
+
+    // Named families get the proportional fonts as fallbacks so icons still render
+    let fallbacks = fonts.families[&egui::FontFamily::Proportional].clone();
+    let mut names: Vec<_> = config.families.iter().collect();
+    names.sort();
+    for (family, spec) in names {
+        let name = add_font(&mut fonts, spec);
+        let mut list = vec![name.clone()];
+        list.extend(fallbacks.iter().filter(|n| **n != name).cloned());
+        fonts
+            .families
+            .insert(egui::FontFamily::Name(family.as_str().into()), list);
+    }
+
+    ctx.set_fonts(fonts);
+}
+
+/// egui's default text styles scaled by the configured font size.
+pub(crate) fn text_styles(size: FontSize) -> BTreeMap<egui::TextStyle, egui::FontId> {
+    let mut styles = egui::Style::default().text_styles;
+    for font_id in styles.values_mut() {
+        font_id.size *= size.factor();
+    }
+    styles
+}
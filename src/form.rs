@@ -0,0 +1,74 @@
+use eframe::egui;
+
+/// Edit an optional setting, treating an empty field as unset. Returns whether it changed.
+pub(crate) fn optional_field(
+    ui: &mut egui::Ui,
+    value: &mut Option<String>,
+    password: bool,
+) -> bool {
+    let mut text = value.clone().unwrap_or_default();
+    let changed = ui
+        .add(egui::TextEdit::singleline(&mut text).password(password))
+        .changed();
+    if changed {
+        *value = (!text.trim().is_empty()).then_some(text);
+    }
+    changed
+}
+
+/// Add a field, disabled and labelled with where its value comes from when an
+/// argument the settings can't change sets it.
+pub(crate) fn fixed_field<R>(
+    ui: &mut egui::Ui,
+    fixed_by: Option<&str>,
+    add: impl FnOnce(&mut egui::Ui) -> R,
+) -> R {
+    ui.horizontal(|ui| {
+        let inner = ui.add_enabled_ui(fixed_by.is_none(), add).inner;
+        if let Some(source) = fixed_by {
+            ui.label(
+                egui::RichText::new(format!("set by {}", source))
+                    .weak()
+                    .small(),
+            );
+        }
+        inner
+    })
+    .inner
+}
+
+/// What was edited in a broker's fields.
+#[derive(Default)]
+pub(crate) struct BrokerEdit {
+    pub(crate) changed: bool,
+    pub(crate) password: bool,
+}
+
+/// Host, username and password rows for a broker, added to the caller's grid.
+/// `fixed_by` gives what sets each of them, if it can't be edited.
+pub(crate) fn broker_rows(
+    ui: &mut egui::Ui,
+    host: &mut String,
+    username: &mut Option<String>,
+    password: &mut Option<String>,
+    fixed_by: [Option<&str>; 3],
+) -> BrokerEdit {
+    let mut edit = BrokerEdit::default();
+
+    ui.label("Host");
+    edit.changed |= fixed_field(ui, fixed_by[0], |ui| {
+        ui.text_edit_singleline(host).changed()
+    });
+    ui.end_row();
+
+    ui.label("Username");
+    edit.changed |= fixed_field(ui, fixed_by[1], |ui| optional_field(ui, username, false));
+    ui.end_row();
+
+    ui.label("Password");
+    edit.password = fixed_field(ui, fixed_by[2], |ui| optional_field(ui, password, true));
+    edit.changed |= edit.password;
+    ui.end_row();
+
+    edit
+}
@@ -0,0 +1,264 @@
+use crate::night::parse_time_of_day;
+use crate::theme;
+use crate::{ApollosKiosk, DataEntry};
+use apollos_types::{CondensedData, GtfsCondensed};
+use chrono::Local;
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Opacity of cards dimmed by a rule.
+const DIM_OPACITY: f32 = 0.35;
+/// Seconds per flash cycle.
+const FLASH_PERIOD: f64 = 1.2;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub(crate) enum CompareOp {
+    #[serde(rename = "<")]
+    Lt,
+    #[serde(rename = "<=")]
+    Le,
+    #[serde(rename = ">")]
+    Gt,
+    #[serde(rename = ">=")]
+    Ge,
+    #[serde(rename = "==")]
+    Eq,
+    #[serde(rename = "!=")]
+    Ne,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub(crate) enum RuleValue {
+    Number(f64),
+    Text(String),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum HighlightStyle {
+    /// Pulse the card border and background in the rule's color
+    Flash,
+    /// Thick border in the rule's color
+    Border,
+    /// Fade the card out
+    Dim,
+}
+
+/// Restyles a card when a field of its data matches, e.g.
+/// `{ cards = "gbfs-*", field = "avail_elec", op = "==", value = 0, style = "dim" }`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct HighlightRule {
+    /// Card keys the rule applies to, where `*` matches anything, e.g. "gtfs-*"
+    pub(crate) cards: String,
+    /// Field in the card's data such as "avail_elec" or "wind.speed"; for GTFS
+    /// cards "departure" is the minutes until each route's next departure
+    pub(crate) field: String,
+    pub(crate) op: CompareOp,
+    pub(crate) value: RuleValue,
+    pub(crate) style: HighlightStyle,
+    /// Defaults to the theme's accent color
+    #[serde(default, deserialize_with = "theme::rgb_format::option")]
+    pub(crate) color: Option<[u8; 3]>,
+}
+
+/// How matching rules change a card's appearance.
+#[derive(Debug, Default)]
+pub(crate) struct CardHighlight {
+    pub(crate) flash: Option<egui::Color32>,
+    pub(crate) border: Option<egui::Color32>,
+    pub(crate) dim: bool,
+}
+
+impl CardHighlight {
+    /// Apply the flash and border styles to a card frame.
+    pub(crate) fn frame(&self, ui: &egui::Ui, frame: egui::Frame) -> egui::Frame {
+        let mut frame = frame;
+        if let Some(color) = self.border {
+            frame = frame.stroke(egui::Stroke::new(3.0, color));
+        }
+        if let Some(color) = self.flash {
+            let time = ui.input(|i| i.time);
+            let pulse = 0.5 + 0.5 * (time * std::f64::consts::TAU / FLASH_PERIOD).sin() as f32;
+            frame = frame
+                .stroke(egui::Stroke::new(3.0, color.gamma_multiply(pulse)))
+                .fill(frame.fill.lerp_to_gamma(color, 0.25 * pulse));
+            ui.ctx().request_repaint_after(Duration::from_millis(50));
+        }
+        frame
+    }
+
+    /// Fade the card's contents if a dim rule matched.
+    pub(crate) fn apply_dim(&self, ui: &mut egui::Ui) {
+        if self.dim {
+            ui.multiply_opacity(DIM_OPACITY);
+        }
+    }
+}
+
+/// Match a card key against a pattern where `*` matches any run of characters.
+fn glob_match(pattern: &str, key: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = key.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No `*` at all: the whole key must match
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// Minutes until a departure given as "14:05", "5 min", "5" or "Now".
+fn minutes_until(time: &str) -> Option<f64> {
+    let time = time.trim();
+    if time.eq_ignore_ascii_case("now") || time.eq_ignore_ascii_case("due") {
+        return Some(0.0);
+    }
+
+    if let Some(at) = parse_time_of_day(time) {
+        let mut minutes = (at - Local::now().time()).num_seconds() as f64 / 60.0;
+        // Times well in the past are after midnight
+        if minutes < -12.0 * 60.0 {
+            minutes += 24.0 * 60.0;
+        }
+        return Some(minutes);
+    }
+
+    let digits: String = time.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+/// The departure shown first on a GTFS card, preferring live times.
+fn next_departure(route: &GtfsCondensed) -> Option<&str> {
+    route
+        .times_live
+        .as_ref()
+        .and_then(|live| live.iter().flatten().next())
+        .or_else(|| route.times.first())
+        .map(String::as_str)
+}
+
+/// Collect every value at a dotted path, looking inside arrays along the way.
+fn field_values<'a>(
+    value: &'a serde_json::Value,
+    path: &[&str],
+    out: &mut Vec<&'a serde_json::Value>,
+) {
+    match value {
+        serde_json::Value::Array(items) => {
+            for item in items {
+                field_values(item, path, out);
+            }
+        }
+        _ => match path.split_first() {
+            None => out.push(value),
+            Some((field, rest)) => {
+                if let Some(inner) = value.get(field) {
+                    field_values(inner, rest, out);
+                }
+            }
+        },
+    }
+}
+
+fn compare(op: CompareOp, actual: &serde_json::Value, expected: &RuleValue) -> bool {
+    match expected {
+        RuleValue::Number(expected) => {
+            let actual = match actual {
+                serde_json::Value::Number(n) => n.as_f64(),
+                serde_json::Value::String(s) => s.trim().parse().ok(),
+                serde_json::Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+                _ => None,
+            };
+            actual.is_some_and(|actual| compare_numbers(op, actual, *expected))
+        }
+        RuleValue::Text(expected) => {
+            let actual = match actual {
+                serde_json::Value::String(s) => s.clone(),
+                serde_json::Value::Null => return false,
+                other => other.to_string(),
+            };
+            match op {
+                CompareOp::Eq => actual.eq_ignore_ascii_case(expected),
+                CompareOp::Ne => !actual.eq_ignore_ascii_case(expected),
+                _ => false,
+            }
+        }
+    }
+}
+
+fn compare_numbers(op: CompareOp, actual: f64, expected: f64) -> bool {
+    match op {
+        CompareOp::Lt => actual < expected,
+        CompareOp::Le => actual <= expected,
+        CompareOp::Gt => actual > expected,
+        CompareOp::Ge => actual >= expected,
+        CompareOp::Eq => actual == expected,
+        CompareOp::Ne => actual != expected,
+    }
+}
+
+impl HighlightRule {
+    /// Whether any value of the rule's field in the card's data satisfies it.
+    fn matches(&self, key: &str, entry: &DataEntry) -> bool {
+        if !glob_match(&self.cards, key) {
+            return false;
+        }
+
+        if self.field == "departure"
+            && let CondensedData::Gtfs(routes) = &entry.content
+        {
+            return routes
+                .iter()
+                .filter_map(next_departure)
+                .filter_map(minutes_until)
+                // Skip departures that have already left but are still listed
+                .filter(|minutes| *minutes > -1.0)
+                .any(|minutes| match &self.value {
+                    RuleValue::Number(expected) => compare_numbers(self.op, minutes, *expected),
+                    RuleValue::Text(_) => false,
+                });
+        }
+
+        let path: Vec<&str> = self.field.split('.').collect();
+        let mut values = Vec::new();
+        field_values(&entry.raw, &path, &mut values);
+        values.iter().any(|v| compare(self.op, v, &self.value))
+    }
+}
+
+impl ApollosKiosk {
+    /// Combine the styles of every highlight rule matching a feed card.
+    pub(crate) fn card_highlight(&self, key: &str, entry: &DataEntry) -> CardHighlight {
+        let mut highlight = CardHighlight::default();
+        let mut accent = None;
+
+        for rule in &self.config.highlight_rules {
+            if !rule.matches(key, entry) {
+                continue;
+            }
+            let color = match rule.color {
+                Some(color) => theme::rgb(color),
+                None => *accent.get_or_insert_with(|| self.get_current_theme().accent()),
+            };
+            match rule.style {
+                HighlightStyle::Flash => highlight.flash = highlight.flash.or(Some(color)),
+                HighlightStyle::Border => highlight.border = highlight.border.or(Some(color)),
+                HighlightStyle::Dim => highlight.dim = true,
+            }
+        }
+
+        highlight
+    }
+}
@@ -0,0 +1,421 @@
+use crate::cards::CardOverride;
+use crate::logging::{CONFIG, THEMES};
+use crate::theme::{Theme, create_default_themes};
+use crate::theme_editor::{new_toml_path, toml_file_name};
+use crate::{ApollosKiosk, Config};
+use eframe::egui;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How an imported layout is combined with the kiosk's own.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ImportMode {
+    /// Use the imported panels and card overrides, moving other cards to the tray
+    Replace,
+    /// Place the imported cards, keeping local cards and overrides the import doesn't mention
+    #[default]
+    Merge,
+}
+
+/// Panels, card overrides and theme of one kiosk, as shared between kiosks.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct LayoutFile {
+    /// Only used for layouts received over MQTT; defaults to merge
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) mode: Option<ImportMode>,
+    pub(crate) panels: Vec<Vec<String>>,
+    #[serde(default)]
+    pub(crate) cards: HashMap<String, CardOverride>,
+    #[serde(default)]
+    pub(crate) theme: Option<Theme>,
+}
+
+/// Parse a layout sent as TOML, like the exported files, or as JSON.
+pub(crate) fn parse_layout(payload: &str) -> Result<LayoutFile, String> {
+    if payload.trim_start().starts_with('{') {
+        serde_json::from_str(payload).map_err(|e| e.to_string())
+    } else {
+        toml::from_str(payload).map_err(|e| e.message().trim().to_string())
+    }
+}
+
+/// Layout files in the layouts directory, sorted by name.
+pub(crate) fn list_layouts(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+                .collect()
+        })
+        .unwrap_or_default();
+    paths.sort();
+    paths
+}
+
+/// Apply an imported layout's panels and card overrides to the config.
+fn merge_layout(config: &mut Config, layout: &LayoutFile, mode: ImportMode) {
+    let panel_count = config.panels.len();
+    let mut panels = layout.panels.clone();
+    panels.resize(panel_count.max(panels.len()), vec![]);
+
+    // Cards on panels this kiosk doesn't have go to the tray
+    let overflow: Vec<String> = panels.drain(panel_count..).flatten().collect();
+    let imported: Vec<&String> = panels.iter().flatten().chain(&overflow).collect();
+
+    let previous: Vec<String> = config
+        .panels
+        .iter()
+        .flatten()
+        .chain(&config.unassigned)
+        .cloned()
+        .collect();
+
+    match mode {
+        ImportMode::Replace => {
+            config.unassigned = previous
+                .into_iter()
+                .filter(|key| !panels.iter().any(|p| p.contains(key)))
+                .collect();
+            for (local, panel) in config.panels.iter_mut().zip(panels) {
+                *local = panel;
+            }
+            config.cards = layout.cards.clone();
+        }
+        ImportMode::Merge => {
+            for local in config.panels.iter_mut().chain([&mut config.unassigned]) {
+                local.retain(|key| !imported.contains(&key));
+            }
+            for (local, panel) in config.panels.iter_mut().zip(panels) {
+                let kept = std::mem::replace(local, panel);
+                local.extend(kept);
+            }
+            config.cards.extend(layout.cards.clone());
+        }
+    }
+
+    for key in overflow {
+        if !config.unassigned.contains(&key) {
+            config.unassigned.push(key);
+        }
+    }
+}
+
+impl ApollosKiosk {
+    /// The current layout in the form written to layout files.
+    pub(crate) fn export_layout(&self) -> LayoutFile {
+        LayoutFile {
+            mode: None,
+            panels: self.config.panels.to_vec(),
+            cards: self.config.cards.clone(),
+            // The selected theme, not the night theme or an editor preview
+            theme: self
+                .themes
+                .iter()
+                .find(|t| t.name == self.current_theme)
+                .cloned(),
+        }
+    }
+
+    /// Write the current layout to the layouts directory.
+    fn export_layout_file(&mut self) {
+        let name = self.layout_export_name.trim();
+        if name.is_empty() {
+            self.layout_status = Some("Enter a name for the layout".to_string());
+            return;
+        }
+
+        let path = self.layouts_dir.join(toml_file_name(name));
+        let result = fs::create_dir_all(&self.layouts_dir)
+            .map_err(|e| e.to_string())
+            .and_then(|_| toml::to_string_pretty(&self.export_layout()).map_err(|e| e.to_string()))
+            .and_then(|s| fs::write(&path, s).map_err(|e| e.to_string()));
+
+        self.layout_status = Some(match result {
+            Ok(()) => {
+                info!(target: CONFIG, "Exported layout to {}", path.display());
+                format!("Exported to {}", path.display())
+            }
+            Err(e) => {
+                warn!(target: CONFIG, "Failed to export {}: {}", path.display(), e);
+                format!("Failed to export: {}", e)
+            }
+        });
+    }
+
+    /// Make an imported theme available and select it, keeping it as a user theme
+    /// unless it is built in.
+    ///
+    /// A user theme of the same name is only replaced by a `Replace` import;
+    /// otherwise the local one is kept and selected.
+    fn install_layout_theme(&mut self, ctx: &egui::Context, mut theme: Theme, mode: ImportMode) {
+        let is_builtin = create_default_themes().iter().any(|t| t.name == theme.name);
+        let existing = self
+            .themes
+            .iter()
+            .find(|t| t.name == theme.name)
+            .and_then(|t| t.path.clone());
+        if existing.is_some() && mode != ImportMode::Replace {
+            info!(
+                target: THEMES,
+                "Keeping the local theme '{}' rather than the layout's",
+                theme.name
+            );
+        } else if !is_builtin {
+            let path = existing.unwrap_or_else(|| new_toml_path(&self.themes_dir, &theme.name));
+            let result = toml::to_string_pretty(&theme)
+                .map_err(|e| e.to_string())
+                .and_then(|s| fs::write(&path, s).map_err(|e| e.to_string()));
+            match result {
+                Ok(()) => {
+                    info!(target: THEMES, "Saved '{}' to {}", theme.name, path.display());
+                    theme.path = Some(path);
+                    theme.remote = false;
+                }
+                Err(e) => {
+                    // Still usable for this session
+                    warn!(target: THEMES, "Failed to save {}: {}", path.display(), e);
+                    theme.path = None;
+                    theme.remote = true;
+                }
+            }
+            self.themes.retain(|t| t.name != theme.name);
+            self.themes.push(theme.clone());
+        }
+
+        self.select_theme(ctx, theme.name);
+    }
+
+    /// Apply a layout from a file or MQTT message.
+    pub(crate) fn import_layout(
+        &mut self,
+        ctx: &egui::Context,
+        layout: LayoutFile,
+        mode: ImportMode,
+    ) {
+        merge_layout(&mut self.config, &layout, mode);
+        self.register_local_cards();
+        self.track_unplaced_feeds();
+
+        match layout.theme {
+            Some(theme) => match theme.validate() {
+                Ok(()) => self.install_layout_theme(ctx, theme, mode),
+                Err(e) => {
+                    warn!(target: THEMES, "Ignoring the layout's theme '{}': {}", theme.name, e);
+                    self.save_config();
+                }
+            },
+            None => self.save_config(),
+        }
+        info!(target: CONFIG, "Imported layout ({:?})", mode);
+    }
+
+    /// Handle a layout received on the layout topic.
+    pub(crate) fn handle_layout_message(&mut self, ctx: &egui::Context, payload: &str) {
+        match parse_layout(payload) {
+            Ok(layout) => {
+                let mode = layout.mode.unwrap_or_default();
+                self.import_layout(ctx, layout, mode);
+            }
+            Err(e) => warn!(target: CONFIG, "Ignoring malformed layout: {}", e),
+        }
+    }
+
+    fn import_layout_file(&mut self, ctx: &egui::Context, path: &Path, mode: ImportMode) {
+        let result = fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| parse_layout(&content));
+
+        self.layout_status = Some(match result {
+            Ok(layout) => {
+                self.import_layout(ctx, layout, mode);
+                format!("Imported {}", path.display())
+            }
+            Err(e) => {
+                warn!(target: CONFIG, "Failed to import {}: {}", path.display(), e);
+                format!("Failed to import {}: {}", path.display(), e)
+            }
+        });
+    }
+
+    pub(crate) fn render_layouts_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_layouts;
+        let mut export = false;
+        let mut import = None;
+        let layouts = list_layouts(&self.layouts_dir);
+
+        egui::Window::new("Layouts")
+            .open(&mut open)
+            .resizable(false)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label(
+                    egui::RichText::new(format!(
+                        "Layout files hold the panels, card overrides and theme. Copy them into {} to use them on another kiosk.",
+                        self.layouts_dir.display()
+                    ))
+                    .weak(),
+                );
+                ui.add_space(8.0);
+
+                ui.heading("Export");
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.layout_export_name)
+                            .hint_text("Layout name"),
+                    );
+                    if ui.button("💾 Export").clicked() {
+                        export = true;
+                    }
+                });
+
+                ui.add_space(8.0);
+                ui.heading("Import");
+                if layouts.is_empty() {
+                    ui.label(egui::RichText::new("No layout files found").weak());
+                }
+                egui::Grid::new("layouts_grid")
+                    .num_columns(3)
+                    .spacing([12.0, 6.0])
+                    .show(ui, |ui| {
+                        for path in &layouts {
+                            let name = path
+                                .file_stem()
+                                .map(|s| s.to_string_lossy().to_string())
+                                .unwrap_or_default();
+                            ui.label(name);
+                            if ui
+                                .button("Merge")
+                                .on_hover_text("Keep cards and overrides the layout doesn't mention")
+                                .clicked()
+                            {
+                                import = Some((path.clone(), ImportMode::Merge));
+                            }
+                            if ui
+                                .button("Replace")
+                                .on_hover_text("Use only the layout's panels; other cards go to the tray")
+                                .clicked()
+                            {
+                                import = Some((path.clone(), ImportMode::Replace));
+                            }
+                            ui.end_row();
+                        }
+                    });
+
+                if let Some(status) = &self.layout_status {
+                    ui.add_space(8.0);
+                    ui.label(status);
+                }
+            });
+
+        if export {
+            self.export_layout_file();
+        }
+        if let Some((path, mode)) = import {
+            self.import_layout_file(ctx, &path, mode);
+        }
+        if !open {
+            self.layout_status = None;
+        }
+        self.show_layouts = open;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(keys: &[&str]) -> Vec<String> {
+        keys.iter().map(|k| k.to_string()).collect()
+    }
+
+    fn titled(title: &str) -> CardOverride {
+        CardOverride {
+            title: Some(title.to_string()),
+            ..CardOverride::default()
+        }
+    }
+
+    /// A kiosk with `a`, `b` and `c` on its panels, `d` in the tray and
+    /// overrides for `a` and `d`.
+    fn local_config() -> Config {
+        Config {
+            panels: [keys(&["a", "b"]), keys(&["c"]), vec![]],
+            unassigned: keys(&["d"]),
+            cards: HashMap::from([
+                ("a".to_string(), titled("Local A")),
+                ("d".to_string(), titled("Local D")),
+            ]),
+            ..Config::default()
+        }
+    }
+
+    fn layout(panels: &[&[&str]]) -> LayoutFile {
+        LayoutFile {
+            mode: None,
+            panels: panels.iter().map(|p| keys(p)).collect(),
+            cards: HashMap::from([("b".to_string(), titled("Imported B"))]),
+            theme: None,
+        }
+    }
+
+    #[test]
+    fn replace_uses_the_imported_panels_and_overrides() {
+        let mut config = local_config();
+        merge_layout(
+            &mut config,
+            &layout(&[&["b"], &[], &["e"]]),
+            ImportMode::Replace,
+        );
+        assert_eq!(config.panels, [keys(&["b"]), vec![], keys(&["e"])]);
+        assert_eq!(config.unassigned, keys(&["a", "c", "d"]));
+        assert_eq!(
+            config.cards,
+            HashMap::from([("b".to_string(), titled("Imported B"))])
+        );
+    }
+
+    #[test]
+    fn merge_places_imported_cards_ahead_of_local_ones() {
+        let mut config = local_config();
+        merge_layout(
+            &mut config,
+            &layout(&[&["c"], &["d"], &["e"]]),
+            ImportMode::Merge,
+        );
+        assert_eq!(
+            config.panels,
+            [keys(&["c", "a", "b"]), keys(&["d"]), keys(&["e"])]
+        );
+        assert!(config.unassigned.is_empty());
+        assert_eq!(config.cards.len(), 3);
+        assert_eq!(config.cards["a"], titled("Local A"));
+        assert_eq!(config.cards["b"], titled("Imported B"));
+    }
+
+    #[test]
+    fn cards_on_extra_panels_go_to_the_tray() {
+        for mode in [ImportMode::Replace, ImportMode::Merge] {
+            let mut config = local_config();
+            merge_layout(&mut config, &layout(&[&[], &[], &[], &["a", "e"]]), mode);
+            assert!(config.panels.iter().flatten().all(|key| key != "a"));
+            for key in ["a", "e"] {
+                let count = config.unassigned.iter().filter(|k| *k == key).count();
+                assert_eq!(count, 1, "{} in the tray on {:?}", key, mode);
+            }
+        }
+    }
+
+    #[test]
+    fn short_layouts_leave_the_remaining_panels_empty_on_replace() {
+        let mut config = local_config();
+        merge_layout(&mut config, &layout(&[&["c"]]), ImportMode::Replace);
+        assert_eq!(config.panels, [keys(&["c"]), vec![], vec![]]);
+        assert_eq!(config.unassigned, keys(&["a", "b", "d"]));
+    }
+}
@@ -0,0 +1,170 @@
+use crate::logging::{KIOSK, MQTT_DATA};
+use crate::{ApollosKiosk, Config};
+use eframe::egui;
+use log::{info, warn};
+use std::time::{Duration, Instant};
+
+/// How long the title must be held down before the unlock prompt appears.
+const UNLOCK_HOLD_DURATION: Duration = Duration::from_secs(3);
+
+/// Shown on the lock buttons while they are disabled.
+pub(crate) const NEEDS_PIN: &str = "Set an unlock PIN first";
+
+impl Config {
+    /// Locking needs a PIN; without one the unlock gesture alone would undo it.
+    pub(crate) fn can_lock(&self) -> bool {
+        self.unlock_pin
+            .as_deref()
+            .is_some_and(|pin| !pin.is_empty())
+    }
+
+    /// Leave a kiosk unlocked when asked to lock it without a PIN, e.g. by
+    /// `--locked` or the config file.
+    pub(crate) fn require_pin_to_lock(&mut self) {
+        if self.locked && !self.can_lock() {
+            warn!(target: KIOSK, "Not locking the kiosk, no unlock PIN is set");
+            self.locked = false;
+        }
+    }
+}
+
+impl ApollosKiosk {
+    pub(crate) fn set_locked(&mut self, locked: bool) {
+        if self.config.locked == locked {
+            return;
+        }
+        if locked && !self.config.can_lock() {
+            warn!(target: KIOSK, "Not locking the kiosk, no unlock PIN is set");
+            return;
+        }
+
+        self.config.locked = locked;
+        if locked {
+            // Close anything that could be used to edit the layout
+            self.show_theme_selector = false;
+            self.show_layouts = false;
+            self.show_config_sources = false;
+            self.settings = None;
+        }
+        self.show_unlock_dialog = false;
+        self.unlock_pin_input.clear();
+        self.unlock_error = false;
+        self.save_config();
+        info!(target: KIOSK, "Kiosk {}", if locked { "locked" } else { "unlocked" });
+    }
+
+    /// Handle a message received on the command topic.
+    ///
+    /// Expects a JSON payload like `{"command": "lock"}` or `{"command": "unlock"}`.
+    pub(crate) fn handle_command(&mut self, payload: &str) {
+        let Some(command) = serde_json::from_str::<serde_json::Value>(payload)
+            .ok()
+            .and_then(|json| json.get("command")?.as_str().map(str::to_lowercase))
+        else {
+            warn!(target: MQTT_DATA, "Ignoring malformed command: {}", payload);
+            return;
+        };
+
+        match command.as_str() {
+            "lock" => self.set_locked(true),
+            "unlock" => self.set_locked(false),
+            _ => warn!(target: MQTT_DATA, "Unknown command: {}", command),
+        }
+    }
+
+    /// Track a long press on the title label; holding it opens the unlock prompt.
+    pub(crate) fn handle_unlock_gesture(&mut self, response: &egui::Response) {
+        if !response.is_pointer_button_down_on() {
+            self.unlock_press_start = None;
+            return;
+        }
+
+        let started = *self.unlock_press_start.get_or_insert_with(Instant::now);
+        if started.elapsed() >= UNLOCK_HOLD_DURATION {
+            self.unlock_press_start = None;
+            if self.config.unlock_pin.as_deref().is_none_or(str::is_empty) {
+                self.set_locked(false);
+            } else {
+                self.show_unlock_dialog = true;
+            }
+        } else {
+            response
+                .ctx
+                .request_repaint_after(Duration::from_millis(100));
+        }
+    }
+
+    pub(crate) fn render_unlock_dialog(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_unlock_dialog;
+        let mut submit = false;
+
+        egui::Window::new("Unlock Kiosk")
+            .open(&mut open)
+            .resizable(false)
+            .collapsible(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label("Enter PIN to unlock editing:");
+                ui.add_space(8.0);
+
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut self.unlock_pin_input)
+                        .password(true)
+                        .desired_width(180.0),
+                );
+                if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    submit = true;
+                }
+
+                if self.unlock_error {
+                    ui.label(
+                        egui::RichText::new("⚠ Incorrect PIN")
+                            .color(egui::Color32::from_rgb(255, 100, 100)),
+                    );
+                }
+
+                ui.add_space(8.0);
+
+                // On-screen keypad, since kiosks usually have no keyboard attached
+                egui::Grid::new("unlock_keypad")
+                    .spacing([8.0, 8.0])
+                    .show(ui, |ui| {
+                        let key_size = egui::vec2(56.0, 48.0);
+                        for row in [["1", "2", "3"], ["4", "5", "6"], ["7", "8", "9"]] {
+                            for digit in row {
+                                if ui.add_sized(key_size, egui::Button::new(digit)).clicked() {
+                                    self.unlock_pin_input.push_str(digit);
+                                }
+                            }
+                            ui.end_row();
+                        }
+
+                        if ui.add_sized(key_size, egui::Button::new("⌫")).clicked() {
+                            self.unlock_pin_input.pop();
+                        }
+                        if ui.add_sized(key_size, egui::Button::new("0")).clicked() {
+                            self.unlock_pin_input.push('0');
+                        }
+                        if ui.add_sized(key_size, egui::Button::new("OK")).clicked() {
+                            submit = true;
+                        }
+                        ui.end_row();
+                    });
+            });
+
+        if submit {
+            if Some(self.unlock_pin_input.as_str()) == self.config.unlock_pin.as_deref() {
+                self.set_locked(false);
+                return;
+            }
+            self.unlock_error = true;
+            self.unlock_pin_input.clear();
+        }
+
+        if !open {
+            self.unlock_pin_input.clear();
+            self.unlock_error = false;
+        }
+        self.show_unlock_dialog = open;
+    }
+}
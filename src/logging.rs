@@ -0,0 +1,260 @@
+use crate::Args;
+use chrono::Local;
+use log::{LevelFilter, Log, Metadata, Record};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+/// Log targets for the kiosk's subsystems, usable in `--log-level`.
+pub(crate) const MQTT_DATA: &str = "mqtt-data";
+pub(crate) const MQTT_THEME: &str = "mqtt-theme";
+pub(crate) const PARSE: &str = "parse";
+pub(crate) const CONFIG: &str = "config";
+pub(crate) const THEMES: &str = "themes";
+pub(crate) const DISPLAY: &str = "display";
+pub(crate) const KIOSK: &str = "kiosk";
+
+/// Start a new log file once the current one reaches this size.
+const LOG_FILE_MAX_SIZE: u64 = 5 * 1024 * 1024;
+/// Rotated log files kept as kiosk.log.1, .2, ...
+const LOG_FILES_KEPT: usize = 3;
+
+/// Log levels as given to `--log-level`, e.g. "info" or "warn,mqtt-data=debug,parse=trace".
+#[derive(Debug, Clone)]
+pub(crate) struct LogFilter {
+    default: LevelFilter,
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl LogFilter {
+    pub(crate) fn parse(spec: &str) -> Result<Self, String> {
+        let mut filter = LogFilter {
+            default: LevelFilter::Info,
+            targets: vec![],
+        };
+
+        for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let parse_level = |level: &str| {
+                LevelFilter::from_str(level.trim())
+                    .map_err(|_| format!("unknown log level '{}'", level.trim()))
+            };
+            match part.split_once('=') {
+                Some((target, level)) => filter
+                    .targets
+                    .push((target.trim().to_string(), parse_level(level)?)),
+                None => filter.default = parse_level(part)?,
+            }
+        }
+        Ok(filter)
+    }
+
+    /// The most specific level configured for a target; `eframe` also covers
+    /// `eframe::native`.
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .filter(|(name, _)| {
+                target == name
+                    || target
+                        .strip_prefix(name.as_str())
+                        .is_some_and(|rest| rest.starts_with("::"))
+            })
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(self.default)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.targets
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, Ord::max)
+    }
+}
+
+/// Back to the `--log-level` form, e.g. for the settings.
+impl fmt::Display for LogFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.default.as_str().to_lowercase())?;
+        for (target, level) in &self.targets {
+            write!(f, ",{}={}", target, level.as_str().to_lowercase())?;
+        }
+        Ok(())
+    }
+}
+
+/// Log file the arguments select: none with `--no-log-file`, otherwise
+/// `--log-file` or the default location.
+pub(crate) fn log_file_path(args: &Args) -> Option<PathBuf> {
+    match (&args.log_file, args.no_log_file) {
+        (_, true) => None,
+        (Some(path), false) => Some(path.clone()),
+        (None, false) => default_log_path(args.instance_name().as_deref()),
+    }
+}
+
+/// Default log file in the state directory, one per profile or `--config` file.
+pub(crate) fn default_log_path(instance: Option<&str>) -> Option<PathBuf> {
+    let dir = dirs::state_dir()
+        .or_else(dirs::data_local_dir)?
+        .join("apollos-kiosk");
+    Some(match instance {
+        Some(instance) => dir.join(format!("kiosk-{}.log", instance)),
+        None => dir.join("kiosk.log"),
+    })
+}
+
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+impl LogFile {
+    fn open(path: &Path) -> std::io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(LogFile {
+            path: path.to_path_buf(),
+            file,
+            size,
+        })
+    }
+
+    /// Shift kiosk.log.1..N along by one and start an empty kiosk.log.
+    fn rotate(&mut self) -> std::io::Result<()> {
+        let _ = fs::remove_file(rotated_path(&self.path, LOG_FILES_KEPT));
+        for n in (1..LOG_FILES_KEPT).rev() {
+            let from = rotated_path(&self.path, n);
+            if from.exists() {
+                fs::rename(&from, rotated_path(&self.path, n + 1))?;
+            }
+        }
+        fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        *self = LogFile::open(&self.path)?;
+        Ok(())
+    }
+
+    fn write_line(&mut self, line: &str) {
+        if self.size + line.len() as u64 > LOG_FILE_MAX_SIZE && self.size > 0 {
+            // Keep writing to the current file if rotating fails
+            let _ = self.rotate();
+        }
+        if self.file.write_all(line.as_bytes()).is_ok() {
+            self.size += line.len() as u64;
+        }
+    }
+}
+
+struct KioskLogger {
+    filter: LogFilter,
+    file: Option<Mutex<LogFile>>,
+}
+
+impl Log for KioskLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = format!(
+            "{} {:<5} [{}] {}\n",
+            Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        let _ = std::io::stderr().write_all(line.as_bytes());
+        if let Some(file) = &self.file
+            && let Ok(mut file) = file.lock()
+        {
+            file.write_line(&line);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file
+            && let Ok(mut file) = file.lock()
+        {
+            let _ = file.file.flush();
+        }
+    }
+}
+
+/// Install the logger, writing to stderr and, if given, a rotating log file.
+pub(crate) fn init(filter: LogFilter, log_file: Option<&Path>) {
+    let mut file_error = None;
+    let file = log_file.and_then(|path| match LogFile::open(path) {
+        Ok(file) => Some(Mutex::new(file)),
+        Err(e) => {
+            file_error = Some(format!("Failed to open log file {}: {}", path.display(), e));
+            None
+        }
+    });
+
+    log::set_max_level(filter.max_level());
+    let logger = Box::leak(Box::new(KioskLogger { filter, file }));
+    if log::set_logger(logger).is_err() {
+        return;
+    }
+
+    match (file_error, log_file) {
+        (Some(e), _) => log::warn!(target: KIOSK, "{}", e),
+        (None, Some(path)) => log::info!(target: KIOSK, "Logging to {}", path.display()),
+        (None, None) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_filters_set_the_default_and_per_target_levels() {
+        let filter = LogFilter::parse(" warn , mqtt-data=debug,parse = trace ").unwrap();
+        assert_eq!(filter.level_for(KIOSK), LevelFilter::Warn);
+        assert_eq!(filter.level_for(MQTT_DATA), LevelFilter::Debug);
+        assert_eq!(filter.level_for(PARSE), LevelFilter::Trace);
+        assert_eq!(filter.max_level(), LevelFilter::Trace);
+        assert_eq!(filter.to_string(), "warn,mqtt-data=debug,parse=trace");
+    }
+
+    #[test]
+    fn log_filters_default_to_info() {
+        let filter = LogFilter::parse("").unwrap();
+        assert_eq!(filter.level_for(CONFIG), LevelFilter::Info);
+        assert_eq!(filter.to_string(), "info");
+    }
+
+    #[test]
+    fn log_filter_targets_cover_their_modules() {
+        let filter = LogFilter::parse("error,eframe=warn,eframe::native=debug").unwrap();
+        assert_eq!(filter.level_for("eframe"), LevelFilter::Warn);
+        assert_eq!(filter.level_for("eframe::web"), LevelFilter::Warn);
+        assert_eq!(filter.level_for("eframe::native::run"), LevelFilter::Debug);
+        assert_eq!(filter.level_for("eframes"), LevelFilter::Error);
+    }
+
+    #[test]
+    fn unknown_log_levels_are_rejected() {
+        let error = LogFilter::parse("info,mqtt-data=loud").unwrap_err();
+        assert_eq!(error, "unknown log level 'loud'");
+        assert!(LogFilter::parse("verbose").is_err());
+    }
+}
@@ -6,155 +6,331 @@ use clap::Parser;
 use eframe::egui;
 use egui::UiKind;
 use egui_material_icons::icons::*;
-use log::info;
+use log::{debug, error, info, trace, warn};
 use paho_mqtt as mqtt;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 use std::fs;
 use std::sync::Arc;
-use std::sync::mpsc::{self, Receiver};
-use std::time::Duration;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Instant;
+
+mod alerts;
+mod cards;
+mod clock;
+mod config_file;
+mod data_mqtt;
+mod env_file;
+mod fonts;
+mod form;
+mod highlight;
+mod layout;
+mod lock;
+mod logging;
+mod night;
+mod profile;
+mod remote_config;
+mod secrets;
+mod settings;
+mod setup;
+mod theme;
+mod theme_editor;
+mod theme_sync;
+mod window;
+
+use alerts::Alert;
+use cards::CardOverride;
+use clock::ClockConfig;
+use data_mqtt::DataConnection;
+use fonts::{FontConfig, FontSize};
+use highlight::HighlightRule;
+use logging::{CONFIG, MQTT_THEME, PARSE, THEMES};
+use night::NightModeConfig;
+use profile::ConfigPaths;
+use remote_config::{ConfigPrecedence, ConfigSource, PushedConfig};
+use secrets::Secret;
+use settings::{SettingsTab, SettingsWindow};
+use setup::SetupWizard;
+use theme::{Theme, ThemeBase, ThemeUpdate, UserThemes, create_default_themes};
+use theme_sync::ThemeSync;
 
 #[derive(Serialize, Deserialize, Clone)]
 struct Config {
+    /// Schema version, used to migrate older config files
+    #[serde(default)]
+    version: u32,
+    /// How many previous versions of the config to keep as config.toml.1, .2, ...
+    #[serde(default = "default_config_backups")]
+    config_backups: usize,
+    /// Whether settings pushed over MQTT or edits made on this kiosk win
+    #[serde(default)]
+    config_precedence: ConfigPrecedence,
+    /// Pushed settings that have been changed on this kiosk since, as dotted
+    /// paths such as `night_mode.theme`
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    local_overrides: BTreeSet<String>,
     panels: [Vec<String>; 3],
     unassigned: Vec<String>,
     #[serde(default)]
     current_theme: String,
+    /// Data broker: a host name, or a URI like "ssl://broker:8883"
+    #[serde(default = "default_mqtt_host")]
+    mqtt_host: String,
+    #[serde(default)]
+    mqtt_username: Option<String>,
+    /// Read from older configs but never written; see `secrets`
+    #[serde(default, skip_serializing)]
+    mqtt_password: Option<String>,
+    /// Topic the data feeds arrive on; the kiosk asks for it on first run
+    #[serde(default)]
+    mqtt_topic: Option<String>,
     #[serde(default)]
     mqtt_theme_sync: bool,
     #[serde(default = "default_theme_mqtt_host")]
     mqtt_theme_host: String,
     #[serde(default)]
     mqtt_theme_username: Option<String>,
-    #[serde(default)]
+    /// Read from older configs but never written; see `secrets`
+    #[serde(default, skip_serializing)]
     mqtt_theme_password: Option<String>,
     #[serde(default = "default_theme_mqtt_topic")]
     mqtt_theme_topic: String,
+    /// Extra theme name mappings for MQTT theme sync (sync name -> kiosk theme);
+    /// names are matched case-insensitively
+    #[serde(default, deserialize_with = "theme::lowercase_aliases")]
+    theme_aliases: HashMap<String, String>,
+    #[serde(default)]
+    locked: bool,
+    /// Read from older configs but never written; see `secrets`
+    #[serde(default, skip_serializing)]
+    unlock_pin: Option<String>,
+    #[serde(default)]
+    display: DisplayConfig,
+    #[serde(default)]
+    fonts: FontConfig,
+    /// Per-card title, subtitle, icon and accent overrides, keyed by card key
+    #[serde(default)]
+    cards: HashMap<String, CardOverride>,
+    /// Rules that restyle cards based on their data, applied in order
+    #[serde(default)]
+    highlight_rules: Vec<HighlightRule>,
+    #[serde(default)]
+    night_mode: NightModeConfig,
+    /// Local clock cards, keyed by card key (which must start with "clock-")
+    #[serde(default = "clock::default_clocks")]
+    clocks: HashMap<String, ClockConfig>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
+            version: config_file::CONFIG_VERSION,
+            config_backups: default_config_backups(),
+            config_precedence: ConfigPrecedence::default(),
+            local_overrides: BTreeSet::new(),
             panels: [vec![], vec![], vec![]],
             unassigned: vec![],
             current_theme: "Dark".to_string(),
+            mqtt_host: default_mqtt_host(),
+            mqtt_username: None,
+            mqtt_password: None,
+            mqtt_topic: None,
             mqtt_theme_sync: false,
             mqtt_theme_host: default_theme_mqtt_host(),
             mqtt_theme_username: None,
             mqtt_theme_password: None,
             mqtt_theme_topic: default_theme_mqtt_topic(),
+            theme_aliases: HashMap::new(),
+            locked: false,
+            unlock_pin: None,
+            display: DisplayConfig::default(),
+            fonts: FontConfig::default(),
+            cards: HashMap::new(),
+            highlight_rules: vec![],
+            night_mode: NightModeConfig::default(),
+            clocks: clock::default_clocks(),
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct Theme {
-    name: String,
-    background_color: [u8; 3],
-    text_color: [u8; 3],
-    accent_color: [u8; 3],
+impl Config {
+    /// Override config values with any given on the command line.
+    fn apply_args(&mut self, args: &Args) {
+        if let Some(host) = &args.mqtt_host {
+            self.mqtt_host = host.clone();
+        }
+        if args.mqtt_username.is_some() {
+            self.mqtt_username = args.mqtt_username.clone();
+        }
+        if args.mqtt_password.is_some() {
+            self.mqtt_password = args.mqtt_password.clone();
+        }
+        if args.mqtt_topic.is_some() {
+            self.mqtt_topic = args.mqtt_topic.clone();
+        }
+        if let Some(sync) = args.mqtt_theme_sync {
+            self.mqtt_theme_sync = sync;
+        }
+        if let Some(host) = &args.mqtt_theme_host {
+            self.mqtt_theme_host = host.clone();
+        }
+        if args.mqtt_theme_username.is_some() {
+            self.mqtt_theme_username = args.mqtt_theme_username.clone();
+        }
+        if args.mqtt_theme_password.is_some() {
+            self.mqtt_theme_password = args.mqtt_theme_password.clone();
+        }
+        if let Some(topic) = &args.mqtt_theme_topic {
+            self.mqtt_theme_topic = topic.clone();
+        }
+        if let Some(locked) = args.locked {
+            self.locked = locked;
+        }
+        if args.unlock_pin.is_some() {
+            self.unlock_pin = args.unlock_pin.clone();
+        }
+        if let Some(resolution) = args.base_resolution {
+            self.display.base_width = resolution.x;
+            self.display.base_height = resolution.y;
+        }
+        if let Some(scale) = args.scale {
+            self.display.scale = scale;
+        }
+        if let Some(density) = args.density {
+            self.display.density = density;
+        }
+        if let Some(font_size) = args.font_size {
+            self.fonts.size = font_size;
+        }
+        if let Some(font) = &args.font {
+            self.fonts.proportional = Some(font.clone());
+        }
+    }
+}
+
+/// Density presets scale all card content on top of the resolution-based scale.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+enum Density {
+    Compact,
+    #[default]
+    Normal,
+    Spacious,
+}
+
+impl Density {
+    const ALL: [Density; 3] = [Density::Compact, Density::Normal, Density::Spacious];
+
+    fn factor(self) -> f32 {
+        match self {
+            Density::Compact => 0.8,
+            Density::Normal => 1.0,
+            Density::Spacious => 1.25,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Density::Compact => "Compact",
+            Density::Normal => "Normal",
+            Density::Spacious => "Spacious",
+        }
+    }
 }
 
-impl Default for Theme {
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct DisplayConfig {
+    /// Resolution the card layout is designed for; content scales relative to it
+    #[serde(default = "default_base_width")]
+    base_width: f32,
+    #[serde(default = "default_base_height")]
+    base_height: f32,
+    /// User multiplier applied on top of the resolution scale
+    #[serde(default = "default_display_scale")]
+    scale: f32,
+    #[serde(default)]
+    density: Density,
+    /// Per-card text scale, keyed by feed key
+    #[serde(default)]
+    card_scale: HashMap<String, f32>,
+}
+
+impl Default for DisplayConfig {
     fn default() -> Self {
-        Theme {
-            name: "Dark".to_string(),
-            background_color: [40, 44, 52],
-            text_color: [220, 223, 228],
-            accent_color: [255, 180, 100],
+        DisplayConfig {
+            base_width: default_base_width(),
+            base_height: default_base_height(),
+            scale: default_display_scale(),
+            density: Density::default(),
+            card_scale: HashMap::new(),
         }
     }
 }
 
-fn create_default_themes() -> Vec<Theme> {
-    vec![
-        Theme {
-            name: "Light".to_string(),
-            background_color: [240, 240, 245],
-            text_color: [60, 60, 70],
-            accent_color: [100, 100, 180],
-        },
-        Theme {
-            name: "Dark".to_string(),
-            background_color: [40, 44, 52],
-            text_color: [220, 223, 228],
-            accent_color: [255, 180, 100],
-        },
-        Theme {
-            name: "Solarized".to_string(),
-            background_color: [0, 43, 54],
-            text_color: [131, 148, 150],
-            accent_color: [181, 137, 0],
-        },
-        Theme {
-            name: "After Dark".to_string(),
-            background_color: [32, 29, 101],
-            text_color: [172, 171, 213],
-            accent_color: [254, 243, 199],
-        },
-        Theme {
-            name: "Her".to_string(),
-            background_color: [101, 29, 29],
-            text_color: [213, 171, 171],
-            accent_color: [254, 243, 199],
-        },
-        Theme {
-            name: "Forest".to_string(),
-            background_color: [5, 46, 22],
-            text_color: [134, 239, 172],
-            accent_color: [254, 243, 199],
-        },
-        Theme {
-            name: "Sky".to_string(),
-            background_color: [8, 47, 73],
-            text_color: [125, 211, 252],
-            accent_color: [254, 243, 199],
-        },
-        Theme {
-            name: "Clays".to_string(),
-            background_color: [69, 26, 3],
-            text_color: [245, 158, 11],
-            accent_color: [254, 243, 199],
-        },
-        Theme {
-            name: "Stones".to_string(),
-            background_color: [41, 37, 36],
-            text_color: [156, 163, 175],
-            accent_color: [254, 243, 199],
-        },
-    ]
+fn default_base_width() -> f32 {
+    1920.0
 }
 
-fn default_theme_mqtt_host() -> String {
+fn default_base_height() -> f32 {
+    1080.0
+}
+
+fn default_display_scale() -> f32 {
+    1.0
+}
+
+fn default_config_backups() -> usize {
+    5
+}
+
+fn default_mqtt_host() -> String {
     "localhost".to_string()
 }
 
+fn default_theme_mqtt_host() -> String {
+    "tcp://localhost:2883".to_string()
+}
+
 fn default_theme_mqtt_topic() -> String {
     "neiam/sync/theme".to_string()
 }
 
 #[derive(Debug, Parser, Clone)]
 struct Args {
+    /// Config file to use instead of the default one
+    #[arg(long, env = "KIOSK_CONFIG")]
+    config: Option<std::path::PathBuf>,
+
+    /// Named profile with its own config and .env, for running several kiosks
+    /// on one machine
+    #[arg(long, env = "KIOSK_PROFILE", value_parser = profile::parse_profile_name, conflicts_with = "config")]
+    profile: Option<String>,
+
     // Data MQTT connection
-    #[arg(long, default_value = "localhost", env = "MQTT_HOST")]
-    mqtt_host: String,
+    #[arg(long, env = "MQTT_HOST")]
+    mqtt_host: Option<String>,
 
+    /// Leave unset for brokers that allow anonymous clients
     #[arg(long, env = "MQTT_USERNAME")]
-    mqtt_username: String,
+    mqtt_username: Option<String>,
 
     #[arg(long, env = "MQTT_PASSWORD")]
-    mqtt_password: String,
+    mqtt_password: Option<String>,
+
+    /// File containing the data MQTT password
+    #[arg(long, env = "MQTT_PASSWORD_FILE")]
+    mqtt_password_file: Option<std::path::PathBuf>,
 
+    /// Topic the data feeds arrive on; without it the kiosk starts with a setup screen
     #[arg(long, env = "MQTT_TOPIC")]
-    mqtt_topic: String,
+    mqtt_topic: Option<String>,
 
     // Theme MQTT connection
     #[arg(long, env = "MQTT_THEME_SYNC")]
     mqtt_theme_sync: Option<bool>,
 
-    #[arg(long, default_value = "tcp://localhost:2883", env = "MQTT_THEME_HOST")]
-    mqtt_theme_host: String,
+    #[arg(long, env = "MQTT_THEME_HOST")]
+    mqtt_theme_host: Option<String>,
 
     #[arg(long, env = "MQTT_THEME_USERNAME")]
     mqtt_theme_username: Option<String>,
@@ -162,234 +338,323 @@ struct Args {
     #[arg(long, env = "MQTT_THEME_PASSWORD")]
     mqtt_theme_password: Option<String>,
 
-    #[arg(long, default_value = "neiam/sync/theme", env = "MQTT_THEME_TOPIC")]
-    mqtt_theme_topic: String,
+    /// File containing the theme MQTT password
+    #[arg(long, env = "MQTT_THEME_PASSWORD_FILE")]
+    mqtt_theme_password_file: Option<std::path::PathBuf>,
+
+    #[arg(long, env = "MQTT_THEME_TOPIC")]
+    mqtt_theme_topic: Option<String>,
+
+    // Remote commands (e.g. {"command": "lock"}) received on the data connection
+    #[arg(long, env = "MQTT_COMMAND_TOPIC")]
+    mqtt_command_topic: Option<String>,
+
+    // Alerts to show on screen (e.g. {"title": "...", "severity": "warning"})
+    #[arg(long, env = "MQTT_ALERT_TOPIC")]
+    mqtt_alert_topic: Option<String>,
+
+    // Layouts to import, in the same format as exported layout files
+    #[arg(long, env = "MQTT_LAYOUT_TOPIC")]
+    mqtt_layout_topic: Option<String>,
+
+    // Retained config documents shared by all kiosks and for this kiosk alone
+    #[arg(long, env = "MQTT_FLEET_CONFIG_TOPIC")]
+    mqtt_fleet_config_topic: Option<String>,
+
+    #[arg(long, env = "MQTT_KIOSK_CONFIG_TOPIC")]
+    mqtt_kiosk_config_topic: Option<String>,
+
+    // Locked mode
+    /// Start locked; ignored with a warning unless an unlock PIN is set
+    #[arg(long, env = "KIOSK_LOCKED", num_args = 0..=1, default_missing_value = "true")]
+    locked: Option<bool>,
+
+    #[arg(long, env = "KIOSK_UNLOCK_PIN")]
+    unlock_pin: Option<String>,
+
+    // Window and display
+    /// Start fullscreen on the monitor the window opens on
+    #[arg(long, env = "KIOSK_FULLSCREEN")]
+    fullscreen: bool,
+
+    /// Remove window decorations (title bar and borders)
+    #[arg(long, env = "KIOSK_BORDERLESS")]
+    borderless: bool,
+
+    /// Initial window position as X,Y; use a monitor's origin to open on that monitor
+    #[arg(long, env = "KIOSK_WINDOW_POSITION", value_parser = window::parse_position)]
+    window_position: Option<egui::Pos2>,
+
+    /// Hide the mouse cursor after this many seconds without pointer activity
+    #[arg(long, env = "KIOSK_HIDE_CURSOR_AFTER")]
+    hide_cursor_after: Option<u64>,
+
+    /// Ignore window close requests
+    #[arg(long, env = "KIOSK_PREVENT_CLOSE")]
+    prevent_close: bool,
+
+    /// Keep the display from blanking or going to sleep
+    #[arg(long, env = "KIOSK_PREVENT_DISPLAY_SLEEP")]
+    prevent_display_sleep: bool,
+
+    // Layout scaling
+    /// Resolution the layout is designed for, as WIDTHxHEIGHT
+    #[arg(long, env = "KIOSK_BASE_RESOLUTION", value_parser = window::parse_resolution)]
+    base_resolution: Option<egui::Vec2>,
+
+    /// Multiplier applied on top of the resolution-based scale
+    #[arg(long, env = "KIOSK_SCALE", value_parser = window::parse_scale)]
+    scale: Option<f32>,
+
+    #[arg(long, value_enum, env = "KIOSK_DENSITY")]
+    density: Option<Density>,
+
+    #[arg(long, value_enum, env = "KIOSK_FONT_SIZE")]
+    font_size: Option<FontSize>,
+
+    /// Font for regular text: "bundled" or a path to a .ttf/.otf file
+    #[arg(long, env = "KIOSK_FONT")]
+    font: Option<String>,
+
+    // Logging
+    /// Log levels, e.g. "info" or "warn,mqtt-data=debug,parse=trace"; targets are
+    /// mqtt-data, mqtt-theme, parse, config, themes, display and kiosk
+    #[arg(long, default_value = "info", env = "KIOSK_LOG", value_parser = logging::LogFilter::parse)]
+    log_level: logging::LogFilter,
+
+    /// Log file, rotated when it grows large; defaults to the state directory
+    #[arg(long, env = "KIOSK_LOG_FILE")]
+    log_file: Option<std::path::PathBuf>,
+
+    /// Only log to stderr
+    #[arg(long, env = "KIOSK_NO_LOG_FILE")]
+    no_log_file: bool,
 }
 
 #[derive(Debug, Clone)]
 struct DataEntry {
     content: CondensedData,
     query_info: Option<QueryInfo>,
+    /// The JSON the content was parsed from, for highlight rules
+    raw: serde_json::Value,
+}
+
+/// What a card shows: a feed received over MQTT or a locally generated widget.
+enum CardSource<'a> {
+    Feed(&'a DataEntry),
+    Clock(&'a ClockConfig),
 }
 
 struct ApollosKiosk {
-    _args: Args,
+    args: Args,
     rx: Receiver<mqtt::Message>,
+    data_tx: Sender<mqtt::Message>,
+    data_connection: Option<DataConnection>,
+    setup: SetupWizard,
+    settings: Option<SettingsWindow>,
     data: HashMap<String, DataEntry>,
     config: Config,
     config_path: std::path::PathBuf,
-    theme_rx: Receiver<String>,
+    /// The `.env` file setup writes to, the profile's own if there is one
+    env_path: std::path::PathBuf,
+    config_rx: Receiver<Result<Config, String>>,
+    config_error: Option<String>,
+    save_error: Option<String>,
+    pushed_config: PushedConfig,
+    show_config_sources: bool,
+    theme_rx: Receiver<ThemeUpdate>,
+    theme_tx: Sender<ThemeUpdate>,
+    theme_sync: Option<ThemeSync>,
+    theme_sync_id: String,
     themes: Vec<Theme>,
+    themes_dir: std::path::PathBuf,
+    secrets_dir: std::path::PathBuf,
+    /// Passwords typed into the settings since they were last stored
+    edited_secrets: HashSet<Secret>,
+    layouts_dir: std::path::PathBuf,
+    user_themes_rx: Receiver<UserThemes>,
+    theme_errors: Vec<String>,
+    theme_editor: Option<Theme>,
+    theme_editor_status: Option<String>,
     current_theme: String,
     show_theme_selector: bool,
-    base_width: f32,
-    base_height: f32,
+    show_layouts: bool,
+    layout_export_name: String,
+    layout_status: Option<String>,
+    show_unlock_dialog: bool,
+    unlock_pin_input: String,
+    unlock_error: bool,
+    unlock_press_start: Option<Instant>,
+    night_active: bool,
+    alerts: VecDeque<Alert>,
+    card_editor: Option<(String, CardOverride)>,
 }
 
 impl ApollosKiosk {
-    fn new(cc: &eframe::CreationContext<'_>, args: Args) -> Self {
-        // Initialize material icons
-        egui_material_icons::initialize(&cc.egui_ctx);
-
+    fn new(cc: &eframe::CreationContext<'_>, args: Args, paths: ConfigPaths) -> Self {
         let (tx, rx) = mpsc::channel();
 
-        let config_path = dirs::config_dir()
-            .unwrap_or_else(|| std::path::PathBuf::from("."))
-            .join("apollos-kiosk")
-            .join("config.toml");
+        let config_path = paths.config;
 
         if let Some(parent) = config_path.parent() {
             let _ = fs::create_dir_all(parent);
         }
 
-        let config: Config = fs::read_to_string(&config_path)
-            .ok()
-            .and_then(|s| toml::from_str(&s).ok())
-            .unwrap_or_default();
+        // A broken config is left untouched on disk rather than replaced by the defaults
+        let (config, config_error) = match config_file::load_config(&config_path) {
+            Ok(config) => {
+                secrets::migrate_config_password(&config, &paths.secrets);
+                (config, None)
+            }
+            Err(e) => {
+                error!(target: CONFIG, "Failed to load {}: {}", config_path.display(), e);
+                (Config::default(), Some(e))
+            }
+        };
+
+        // A password or PIN left in the file by older versions is rewritten out of it below
+        let scrub_password = config.has_secrets();
 
         // Merge args with config (args take precedence)
         let mut config = config;
-        if let Some(sync) = args.mqtt_theme_sync {
-            config.mqtt_theme_sync = sync;
-        }
-        config.mqtt_theme_host = args.mqtt_theme_host.clone();
-        if args.mqtt_theme_username.is_some() {
-            config.mqtt_theme_username = args.mqtt_theme_username.clone();
-        }
-        if args.mqtt_theme_password.is_some() {
-            config.mqtt_theme_password = args.mqtt_theme_password.clone();
+        config.apply_args(&args);
+        config.load_stored_secrets(&paths.secrets);
+        config.require_pin_to_lock();
+        let config_rx = config_file::spawn_config_watcher(config_path.clone(), cc.egui_ctx.clone());
+
+        // Configured fonts, the bundled fallback font and material icons
+        fonts::install_fonts(&cc.egui_ctx, &config.fonts);
+
+        // User themes live next to the config and are reloaded when they change
+        let themes_dir = paths.themes;
+        let _ = fs::create_dir_all(&themes_dir);
+
+        let user_themes = theme::load_user_themes(&themes_dir);
+        let mut themes = create_default_themes();
+        themes.extend(user_themes.themes);
+        // Local themes win over definitions received earlier with the same name
+        for received in theme::load_received_themes(&themes_dir) {
+            if !themes
+                .iter()
+                .any(|t| t.name.eq_ignore_ascii_case(&received.name))
+            {
+                themes.push(received);
+            }
         }
-        config.mqtt_theme_topic = args.mqtt_theme_topic.clone();
+        let theme_errors = user_themes.errors;
+        let user_themes_rx = theme::spawn_theme_watcher(themes_dir.clone(), cc.egui_ctx.clone());
 
-        let themes = create_default_themes();
         let current_theme = config.current_theme.clone();
 
-        // Apply initial theme
-        let theme = themes
-            .iter()
-            .find(|t| t.name == current_theme)
-            .cloned()
-            .unwrap_or_default();
-
-        let mut visuals = egui::Visuals::dark();
-        visuals.panel_fill = egui::Color32::from_rgb(
-            theme.background_color[0],
-            theme.background_color[1],
-            theme.background_color[2],
-        );
-        visuals.override_text_color = Some(egui::Color32::from_rgb(
-            theme.text_color[0],
-            theme.text_color[1],
-            theme.text_color[2],
-        ));
-        cc.egui_ctx.set_visuals(visuals);
-
         let (theme_tx, theme_rx) = mpsc::channel();
         let mqtt_theme_sync = config.mqtt_theme_sync;
 
-        // Setup data MQTT connection in a background thread
-        let mqtt_args = args.clone();
-        let ctx = cc.egui_ctx.clone();
-        let value = args.clone();
-
-        std::thread::spawn(move || {
-            let args = value.clone();
-            let create_opts = mqtt::CreateOptionsBuilder::new()
-                .server_uri(format!("tcp://{}:1883", mqtt_args.mqtt_host))
-                .client_id(args.mqtt_username)
-                .finalize();
-
-            let cli = mqtt::Client::new(create_opts).expect("Error creating MQTT client");
-            let rx_mqtt = cli.start_consuming();
-
-            let conn_opts = mqtt::ConnectOptionsBuilder::new()
-                .keep_alive_interval(Duration::from_secs(20))
-                .clean_session(true)
-                .automatic_reconnect(Duration::from_secs(1), Duration::from_secs(30))
-                .user_name(mqtt_args.mqtt_username)
-                .password(mqtt_args.mqtt_password)
-                .finalize();
-
-            if let Err(e) = cli.connect(conn_opts) {
-                eprintln!("Error connecting to MQTT: {:?}", e);
-                return;
-            }
-
-            if let Err(e) = cli.subscribe(&mqtt_args.mqtt_topic, 1) {
-                eprintln!("Error subscribing to topic: {:?}", e);
-                return;
-            }
-
-            println!(
-                "Data MQTT: Connected and subscribed to {}",
-                mqtt_args.mqtt_topic
-            );
-
-            for msg in rx_mqtt.iter() {
-                if let Some(msg) = msg {
-                    println!("MQTT: Received message on topic '{}'", msg.topic());
-                    let _ = tx.send(msg);
-                    ctx.request_repaint();
-                } else {
-                    // None indicates a disconnection, but with auto-reconnect enabled
-                    // the client will handle reconnection automatically
-                    println!("Data MQTT: Disconnected, waiting for reconnection...");
-                }
-            }
-        });
+        // Setup data MQTT connection; without a topic the setup screen is shown instead
+        let data_connection =
+            DataConnection::start(&config, &args, tx.clone(), cc.egui_ctx.clone(), None);
 
         // Setup separate MQTT connection for theme sync
-        if mqtt_theme_sync {
-            let theme_host = config.mqtt_theme_host.clone();
-            let theme_username = config
-                .mqtt_theme_username
-                .clone()
-                .unwrap_or_else(|| "kiosk-theme".to_string());
-            let theme_password = config.mqtt_theme_password.clone().unwrap_or_default();
-            let theme_topic = config.mqtt_theme_topic.clone();
-            let theme_ctx = cc.egui_ctx.clone();
-
-            std::thread::spawn(move || {
-                let create_opts = mqtt::CreateOptionsBuilder::new()
-                    .server_uri(theme_host)
-                    .client_id(format!("{}-client", theme_username))
-                    .finalize();
-
-                let cli = mqtt::Client::new(create_opts).expect("Error creating theme MQTT client");
-                let rx_mqtt = cli.start_consuming();
-
-                let conn_opts = mqtt::ConnectOptionsBuilder::new()
-                    .keep_alive_interval(Duration::from_secs(20))
-                    .clean_session(true)
-                    .automatic_reconnect(Duration::from_secs(1), Duration::from_secs(30))
-                    .user_name(&theme_username)
-                    .password(&theme_password)
-                    .finalize();
-
-                if let Err(e) = cli.connect(conn_opts) {
-                    eprintln!("Error connecting to theme MQTT: {:?}", e);
-                    return;
-                }
-
-                if let Err(e) = cli.subscribe(&theme_topic, 1) {
-                    eprintln!(
-                        "Error subscribing to theme topic '{}': {:?}",
-                        theme_topic, e
-                    );
-                    return;
-                }
-
-                println!("Theme MQTT: Connected and subscribed to {}", theme_topic);
-
-                for msg in rx_mqtt.iter() {
-                    if let Some(msg) = msg {
-                        println!("Theme MQTT: Received message on topic '{}'", msg.topic());
-
-                        if msg.topic() == theme_topic
-                            && let Ok(json) =
-                                serde_json::from_str::<serde_json::Value>(&msg.payload_str())
-                            && let Some(theme_name) = json.get("theme").and_then(|t| t.as_str())
-                        {
-                            println!("Theme MQTT: Parsed theme name: {}", theme_name);
-                            let _ = theme_tx.send(theme_name.to_string());
-                            theme_ctx.request_repaint();
-                        }
-                    } else {
-                        // None indicates a disconnection, but with auto-reconnect enabled
-                        // the client will handle reconnection automatically
-                        println!("Theme MQTT: Disconnected, waiting for reconnection...");
-                    }
-                }
-            });
-        }
+        let theme_sync_id = theme_sync::sync_source_id();
+        let theme_sync = if mqtt_theme_sync {
+            ThemeSync::start(
+                &config,
+                theme_sync_id.clone(),
+                args.instance_name().as_deref(),
+                theme_tx.clone(),
+                cc.egui_ctx.clone(),
+                None,
+            )
+        } else {
+            None
+        };
 
-        Self {
-            _args: args,
+        let setup = SetupWizard::new(&args);
+        let mut kiosk = Self {
+            args,
             rx,
+            data_tx: tx,
+            data_connection,
+            setup,
+            settings: None,
             data: HashMap::new(),
             config,
             config_path,
+            env_path: paths.env_files[0].clone(),
+            config_rx,
+            config_error,
+            save_error: None,
+            pushed_config: PushedConfig::default(),
+            show_config_sources: false,
             theme_rx,
+            theme_tx,
+            theme_sync,
+            theme_sync_id,
             themes,
+            themes_dir,
+            secrets_dir: paths.secrets,
+            edited_secrets: HashSet::new(),
+            layouts_dir: paths.layouts,
+            user_themes_rx,
+            theme_errors,
+            theme_editor: None,
+            theme_editor_status: None,
             current_theme,
             show_theme_selector: false,
-            base_width: 1920.0,
-            base_height: 1080.0,
+            show_layouts: false,
+            layout_export_name: String::new(),
+            layout_status: None,
+            show_unlock_dialog: false,
+            unlock_pin_input: String::new(),
+            unlock_error: false,
+            unlock_press_start: None,
+            night_active: false,
+            alerts: VecDeque::new(),
+            card_editor: None,
+        };
+        kiosk.register_local_cards();
+        if scrub_password {
+            kiosk.scrub_config_password();
         }
+
+        // Apply initial theme
+        kiosk.apply_theme(&cc.egui_ctx);
+        kiosk
     }
 
-    fn save_config(&self) {
-        let mut cfg = self.config.clone();
-        cfg.current_theme = self.current_theme.clone();
-        if let Ok(s) = toml::to_string_pretty(&self.config) {
-            let _ = fs::write(&self.config_path, s);
+    fn save_config(&mut self) {
+        if self.config_error.is_some() {
+            warn!(
+                target: CONFIG,
+                "Not saving over {}, it has errors",
+                self.config_path.display()
+            );
+            return;
+        }
+
+        // Note pushed settings changed here, so they can be kept over the next push
+        let mut snapshot = self.config_snapshot();
+        self.pushed_config.track_local_edits(&mut snapshot);
+        self.config.local_overrides = snapshot.local_overrides.clone();
+
+        let backups = self.config.config_backups;
+        match config_file::write_config(&self.config_path, &snapshot, backups) {
+            Ok(()) => self.save_error = None,
+            Err(e) => {
+                error!(
+                    target: CONFIG,
+                    "Failed to save {}: {}",
+                    self.config_path.display(),
+                    e
+                );
+                self.save_error = Some(e);
+            }
         }
     }
 
-    fn render_data_item(ui: &mut egui::Ui, key: &str, content: &CondensedData) {
+    fn render_data_item(&self, ui: &mut egui::Ui, key: &str, content: &CondensedData) {
         let card_frame = egui::Frame::group(ui.style())
             .fill(ui.visuals().faint_bg_color)
-            .corner_radius(8.0)
+            .corner_radius(self.get_current_theme().corner_radius())
             .inner_margin(12.0)
             .outer_margin(egui::Margin::symmetric(0, 4));
 
@@ -440,11 +705,11 @@ impl ApollosKiosk {
                         ui.label(egui::RichText::new(&s.name).strong());
                         ui.add_space(2.0);
                         ui.horizontal(|ui| {
-                            ui.label(format!("🚲 {}", s.avail));
+                            ui.label(format!("{} {}", ICON_PEDAL_BIKE, s.avail));
                             ui.separator();
-                            ui.label(format!("⚡ {}", s.avail_elec));
+                            ui.label(format!("{} {}", ICON_ELECTRIC_BIKE, s.avail_elec));
                             ui.separator();
-                            ui.label(format!("🅿 {}", s.docks_avail));
+                            ui.label(format!("{} {}", ICON_LOCAL_PARKING, s.docks_avail));
                         });
                     }
                 }
@@ -461,7 +726,10 @@ impl ApollosKiosk {
                                 ui.label(format!("Feels like {:.0}°", w.feel));
                             });
                         });
-                        ui.small(format!("Wind: {}mph | Hum: {}%", w.wind.speed, w.hum));
+                        ui.small(format!(
+                            "{} {}mph  {} {}%",
+                            ICON_AIR, w.wind.speed, ICON_WATER_DROP, w.hum
+                        ));
                     }
                 }
                 CondensedData::Calendar(events) => {
@@ -476,16 +744,20 @@ impl ApollosKiosk {
                         ui.label(
                             egui::RichText::new(a.name.as_deref().unwrap_or("Unknown")).strong(),
                         );
-                        ui.label(format!("Measurements: {}", a.measurements.len()));
+                        ui.label(format!(
+                            "{} {} measurements",
+                            ICON_MONITORING,
+                            a.measurements.len()
+                        ));
                     }
                 }
                 CondensedData::Tidal(reports) => {
                     for t in reports {
                         if let Some(h) = &t.first_h {
-                            ui.label(format!("⬆ High: {}", h));
+                            ui.label(format!("{} High: {}", ICON_NORTH, h));
                         }
                         if let Some(l) = &t.first_l {
-                            ui.label(format!("⬇ Low: {}", l));
+                            ui.label(format!("{} Low: {}", ICON_SOUTH, l));
                         }
                     }
                 }
@@ -499,41 +771,78 @@ impl ApollosKiosk {
 
 impl eframe::App for ApollosKiosk {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.update_window(ctx);
+        self.update_night_mode(ctx);
+
         // Check for incoming theme updates from MQTT
-        if let Ok(theme_name) = self.theme_rx.try_recv() {
-            let kiosk_theme = match theme_name.to_lowercase().as_str() {
-                "light" | "light-soft" => "Light",
-                "dark" | "dark-soft" | "dark-dimmed" => "Dark",
-                "after-dark" => "After Dark",
-                "her" => "Her",
-                "forest" => "Forest",
-                "sky" => "Sky",
-                "clays" => "Clays",
-                "stones" => "Stones",
-                "solarized" => "Solarized",
-                _ => "",
-            };
+        if let Ok(update) = self.theme_rx.try_recv() {
+            let mut kiosk_theme = None;
+            if let Some(definition) = update.definition {
+                kiosk_theme = Some(self.add_remote_theme(definition));
+            }
 
-            if !kiosk_theme.is_empty() && kiosk_theme != self.current_theme {
-                self.current_theme = kiosk_theme.to_string();
-                self.apply_theme(ctx);
-                self.save_config();
-                println!("Theme updated via MQTT to: {}", kiosk_theme);
-            } else if kiosk_theme.is_empty() {
-                eprintln!("Unknown theme from MQTT: {}, keeping current", theme_name);
+            match kiosk_theme.or_else(|| self.resolve_theme_name(&update.name)) {
+                Some(kiosk_theme) if kiosk_theme != self.current_theme => {
+                    self.current_theme = kiosk_theme;
+                    self.apply_theme(ctx);
+                    self.save_config();
+                    info!(target: MQTT_THEME, "Theme updated to {}", self.current_theme);
+                }
+                Some(_) => {
+                    // Definitions may have changed even if the name didn't
+                    self.apply_theme(ctx);
+                }
+                None => {
+                    warn!(target: MQTT_THEME, "Unknown theme {}, keeping current", update.name);
+                }
             }
         }
 
+        // Pick up edits to the config file
+        if let Ok(loaded) = self.config_rx.try_recv() {
+            self.reload_config(ctx, loaded);
+        }
+
+        // Pick up added, changed or removed theme files
+        if let Ok(user_themes) = self.user_themes_rx.try_recv() {
+            let remote_themes: Vec<Theme> = self.themes.drain(..).filter(|t| t.remote).collect();
+            self.themes = create_default_themes();
+            self.themes.extend(user_themes.themes);
+            self.themes.extend(remote_themes);
+            self.theme_errors = user_themes.errors;
+            self.apply_theme(ctx);
+            info!(target: THEMES, "Loaded {} themes", self.themes.len());
+        }
+
         // Receive and parse any pending messages
         while let Ok(msg) = self.rx.try_recv() {
             let payload = msg.payload_str();
 
+            if self.args.mqtt_command_topic.as_deref() == Some(msg.topic()) {
+                self.handle_command(&payload);
+                continue;
+            }
+            if self.args.mqtt_alert_topic.as_deref() == Some(msg.topic()) {
+                self.handle_alert(&payload);
+                continue;
+            }
+            if self.args.mqtt_layout_topic.as_deref() == Some(msg.topic()) {
+                self.handle_layout_message(ctx, &payload);
+                continue;
+            }
+            if self.args.mqtt_fleet_config_topic.as_deref() == Some(msg.topic()) {
+                self.handle_pushed_config(ctx, ConfigSource::Fleet, &payload);
+                continue;
+            }
+            if self.args.mqtt_kiosk_config_topic.as_deref() == Some(msg.topic()) {
+                self.handle_pushed_config(ctx, ConfigSource::Kiosk, &payload);
+                continue;
+            }
+
             if let Ok(raw_map) =
                 serde_json::from_str::<HashMap<String, serde_json::Value>>(&payload)
             {
-                if let Ok(pretty) = serde_json::to_string_pretty(&raw_map) {
-                    println!("MQTT: Received valid data map:\n{}", pretty);
-                }
+                trace!(target: PARSE, "Data payload: {}", payload);
 
                 for (key, value) in raw_map {
                     if let Some(entry) = self.parse_data_entry(&key, &value) {
@@ -544,7 +853,7 @@ impl eframe::App for ApollosKiosk {
                                 || self.config.unassigned.contains(&key);
 
                             if !is_assigned {
-                                println!("  - Adding {} to unassigned", key);
+                                info!(target: PARSE, "Adding new feed {} to unassigned", key);
                                 self.config.unassigned.push(key.clone());
                                 self.save_config();
                             }
@@ -552,28 +861,81 @@ impl eframe::App for ApollosKiosk {
 
                         self.data.insert(key, entry);
                     } else {
-                        println!("  - Failed to parse data for key: {}", key);
+                        warn!(target: PARSE, "Failed to parse data for {}", key);
                     }
                 }
             } else {
-                println!("MQTT: Received payload that is not a Map: {}", payload);
+                warn!(target: PARSE, "Ignoring payload that is not a map: {}", payload);
             }
         }
 
         egui::TopBottomPanel::bottom("top_bar").show(ctx, |ui| {
             ui.horizontal(|ui| {
-                ui.heading("Apollos Kiosk");
+                // Holding the title while locked opens the unlock prompt
+                let title = ui.add(
+                    egui::Label::new(egui::RichText::new("Apollos Kiosk").heading())
+                        .sense(egui::Sense::click()),
+                );
+                if self.config.locked {
+                    self.handle_unlock_gesture(&title);
+                }
+
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                    // Theme selector button
-                    if ui
-                        .button(egui::RichText::new(ICON_PALETTE).size(20.0))
-                        .clicked()
-                    {
-                        self.show_theme_selector = !self.show_theme_selector;
-                    }
+                    if !self.config.locked {
+                        // Theme selector button
+                        if ui
+                            .button(egui::RichText::new(ICON_PALETTE).size(20.0))
+                            .clicked()
+                        {
+                            self.show_theme_selector = !self.show_theme_selector;
+                        }
+
+                        if ui
+                            .button(egui::RichText::new(ICON_DASHBOARD).size(20.0))
+                            .on_hover_text("Import or export layout")
+                            .clicked()
+                        {
+                            self.show_layouts = !self.show_layouts;
+                        }
+
+                        let has_config_topics = self.args.mqtt_fleet_config_topic.is_some()
+                            || self.args.mqtt_kiosk_config_topic.is_some();
+                        if has_config_topics
+                            && ui
+                                .button(egui::RichText::new(ICON_CLOUD).size(20.0))
+                                .on_hover_text("Config sources")
+                                .clicked()
+                        {
+                            self.show_config_sources = !self.show_config_sources;
+                        }
+
+                        if ui
+                            .button(egui::RichText::new(ICON_SETTINGS).size(20.0))
+                            .on_hover_text("Settings")
+                            .clicked()
+                        {
+                            self.settings = match self.settings {
+                                Some(_) => None,
+                                None => Some(SettingsWindow::new(&self.args, &self.env_path)),
+                            };
+                        }
+
+                        if ui
+                            .add_enabled(
+                                self.config.can_lock(),
+                                egui::Button::new(egui::RichText::new(ICON_LOCK).size(20.0)),
+                            )
+                            .on_hover_text("Lock kiosk")
+                            .on_disabled_hover_text(lock::NEEDS_PIN)
+                            .clicked()
+                        {
+                            self.set_locked(true);
+                        }
 
-                    ui.separator();
+                        ui.separator();
+                    }
                     ui.label(format!("{} data feeds", self.data.len()));
+                    self.show_data_connection_status(ui);
                 });
             });
         });
@@ -583,13 +945,59 @@ impl eframe::App for ApollosKiosk {
             self.render_theme_selector(ctx);
         }
 
+        if self.show_layouts {
+            self.render_layouts_window(ctx);
+        }
+
+        if self.show_config_sources {
+            self.render_config_sources(ctx);
+        }
+
+        if self.settings.is_some() {
+            self.render_settings_window(ctx);
+        }
+
+        if self.theme_editor.is_some() {
+            if self.config.locked {
+                // Locking discards unsaved edits and their preview
+                self.close_theme_editor(ctx);
+            } else {
+                self.render_theme_editor(ctx);
+            }
+        }
+
+        if self.card_editor.is_some() {
+            if self.config.locked {
+                self.card_editor = None;
+            } else {
+                self.render_card_editor(ctx);
+            }
+        }
+
+        if self.show_unlock_dialog {
+            self.render_unlock_dialog(ctx);
+        }
+
+        self.render_config_error(ctx);
+        self.update_alerts(ctx);
+        self.render_alert_banner(ctx);
+
         egui::CentralPanel::default().show(ctx, |ui| {
+            if self.night_active && self.config.night_mode.clock_only {
+                self.render_night_clock(ui);
+                return;
+            }
+            if self.data_connection.is_none() {
+                self.render_setup_wizard(ui);
+                return;
+            }
+
             egui::ScrollArea::vertical().show(ui, |ui| {
                 // Show unassigned items at the top
-                if !self.config.unassigned.is_empty() {
+                if !self.config.locked && !self.config.unassigned.is_empty() {
                     egui::Frame::group(ui.style())
                         .fill(ui.visuals().extreme_bg_color)
-                        .corner_radius(8.0)
+                        .corner_radius(self.get_current_theme().corner_radius())
                         .inner_margin(12.0)
                         .outer_margin(8.0)
                         .show(ui, |ui| {
@@ -604,7 +1012,11 @@ impl eframe::App for ApollosKiosk {
                                 let mut to_move = None;
 
                                 for (idx, key) in self.config.unassigned.iter().enumerate() {
-                                    ui.menu_button(format!("📌 {}", key), |ui| {
+                                    let label = self
+                                        .card_override(key)
+                                        .and_then(|o| o.title.as_deref())
+                                        .unwrap_or(key);
+                                    ui.menu_button(format!("📌 {}", label), |ui| {
                                         ui.label(egui::RichText::new("Assign to panel:").strong());
                                         ui.separator();
                                         if ui.button("Panel 1 (Left)").clicked() {
@@ -640,15 +1052,31 @@ impl eframe::App for ApollosKiosk {
                 });
             });
         });
+
+        if self.night_active {
+            self.render_night_overlay(ctx);
+        }
+
+        self.render_alert_takeover(ctx);
     }
 }
 
 impl ApollosKiosk {
     fn get_scale_factor(&self, ctx: &egui::Context) -> f32 {
+        let display = &self.config.display;
         let viewport_size = ctx.content_rect().size();
-        let scale_x = viewport_size.x / self.base_width;
-        let scale_y = viewport_size.y / self.base_height;
-        scale_x.min(scale_y)
+        let scale_x = viewport_size.x / display.base_width.max(1.0);
+        let scale_y = viewport_size.y / display.base_height.max(1.0);
+        scale_x.min(scale_y) * window::clamp_scale(display.scale) * display.density.factor()
+    }
+
+    fn card_scale(&self, key: &str) -> f32 {
+        self.config
+            .display
+            .card_scale
+            .get(key)
+            .copied()
+            .unwrap_or(1.0)
     }
 
     fn render_panel(&mut self, ui: &mut egui::Ui, panel_idx: usize) {
@@ -656,7 +1084,7 @@ impl ApollosKiosk {
 
         egui::Frame::group(ui.style())
             .fill(ui.visuals().panel_fill)
-            .corner_radius(8.0)
+            .corner_radius(self.get_current_theme().corner_radius())
             .inner_margin(8.0)
             .show(ui, |ui| {
                 ui.set_min_height(ui.available_height());
@@ -677,21 +1105,36 @@ impl ApollosKiosk {
                 let keys = self.config.panels[panel_idx].clone();
                 let mut to_remove = None;
                 let mut to_move = None;
+                let mut to_rescale = None;
+                let mut to_edit = None;
 
-                let scale = self.get_scale_factor(ui.ctx());
+                let scale = self.get_scale_factor(ui.ctx()) * self.config.fonts.size.factor();
                 for (idx, key) in keys.iter().enumerate() {
-                    if let Some(entry) = self.data.get(key) {
-                        self.render_large_card(
-                            ui,
-                            key,
-                            entry,
-                            panel_idx,
-                            idx,
-                            &mut to_remove,
-                            &mut to_move,
-                            scale,
-                        );
-                    }
+                    let source = match self.data.get(key) {
+                        Some(entry) => CardSource::Feed(entry),
+                        None => match self.config.clocks.get(key) {
+                            Some(clock) if key.starts_with(clock::CLOCK_KEY_PREFIX) => {
+                                CardSource::Clock(clock)
+                            }
+                            _ => continue,
+                        },
+                    };
+                    self.render_large_card(
+                        ui,
+                        key,
+                        source,
+                        panel_idx,
+                        idx,
+                        &mut to_remove,
+                        &mut to_move,
+                        &mut to_rescale,
+                        &mut to_edit,
+                        scale * self.card_scale(key),
+                    );
+                }
+
+                if let Some(idx) = to_edit {
+                    self.open_card_editor(self.config.panels[panel_idx][idx].clone());
                 }
 
                 // Handle card removal
@@ -707,6 +1150,17 @@ impl ApollosKiosk {
                     self.config.panels[target_panel].push(key);
                     self.save_config();
                 }
+
+                // Handle per-card text scale changes
+                if let Some((idx, card_scale)) = to_rescale {
+                    let key = self.config.panels[panel_idx][idx].clone();
+                    if (card_scale - 1.0).abs() < f32::EPSILON {
+                        self.config.display.card_scale.remove(&key);
+                    } else {
+                        self.config.display.card_scale.insert(key, card_scale);
+                    }
+                    self.save_config();
+                }
             });
     }
 
@@ -714,46 +1168,84 @@ impl ApollosKiosk {
         &self,
         ui: &mut egui::Ui,
         key: &str,
-        entry: &DataEntry,
+        source: CardSource,
         panel_idx: usize,
         card_idx: usize,
         to_remove: &mut Option<usize>,
         to_move: &mut Option<(usize, usize)>,
+        to_rescale: &mut Option<(usize, f32)>,
+        to_edit: &mut Option<usize>,
         scale: f32,
     ) {
+        let theme = self.get_current_theme();
+        let highlight = match &source {
+            CardSource::Feed(entry) => self.card_highlight(key, entry),
+            CardSource::Clock(_) => highlight::CardHighlight::default(),
+        };
+        let card_override = self.card_override(key);
+        let accent_color = card_override
+            .and_then(|o| o.accent)
+            .map(theme::rgb)
+            .unwrap_or_else(|| theme.accent());
         let card_frame = egui::Frame::group(ui.style())
             .fill(ui.visuals().faint_bg_color)
             .stroke(egui::Stroke::new(
                 1.0,
                 ui.visuals().widgets.noninteractive.bg_stroke.color,
             ))
-            .corner_radius(12.0)
+            .corner_radius(theme.corner_radius())
             .inner_margin(16.0)
             .outer_margin(egui::Margin::symmetric(0, 8));
 
-        card_frame.show(ui, |ui| {
+        highlight.frame(ui, card_frame).show(ui, |ui| {
             ui.set_min_width(ui.available_width());
+            highlight.apply_dim(ui);
 
             // Card header with title and controls
             ui.horizontal(|ui| {
-                // Use query name if available, otherwise fall back to key
-                let display_name = entry
-                    .query_info
-                    .as_ref()
-                    .map(|q| q.name.as_str())
-                    .unwrap_or(key);
-
-                ui.label(
-                    egui::RichText::new(display_name)
-                        .heading()
-                        .strong()
-                        .size(18.0 * scale),
-                );
+                // Use the configured title, then the query name, then the key
+                let display_name = match &source {
+                    CardSource::Feed(entry) => entry
+                        .query_info
+                        .as_ref()
+                        .map(|q| q.name.as_str())
+                        .unwrap_or(key),
+                    CardSource::Clock(clock) => clock.title.as_deref().unwrap_or("Clock"),
+                };
+                let display_name = card_override
+                    .and_then(|o| o.title.as_deref())
+                    .unwrap_or(display_name);
+
+                if let Some(icon) = card_override.and_then(|o| o.icon.as_deref()) {
+                    ui.label(
+                        egui::RichText::new(cards::icon_glyph(icon))
+                            .size(22.0 * scale)
+                            .color(accent_color),
+                    );
+                }
+                ui.vertical(|ui| {
+                    ui.label(
+                        egui::RichText::new(display_name)
+                            .heading()
+                            .strong()
+                            .size(18.0 * scale),
+                    );
+                    if let Some(subtitle) = card_override.and_then(|o| o.subtitle.as_deref()) {
+                        ui.label(egui::RichText::new(subtitle).weak().size(14.0 * scale));
+                    }
+                });
 
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                     ui.label(egui::RichText::new(key).weak().small());
+                    if self.config.locked {
+                        return;
+                    }
                     ui.add_space(8.0);
                     ui.menu_button("⋮", |ui| {
+                        if ui.button("✏ Edit Card...").clicked() {
+                            *to_edit = Some(card_idx);
+                            ui.close();
+                        }
                         if ui.button("🗑 Unassign").clicked() {
                             *to_remove = Some(card_idx);
                             ui.close();
@@ -766,6 +1258,18 @@ impl ApollosKiosk {
                                 ui.close();
                             }
                         }
+                        ui.separator();
+                        ui.label("Text size:");
+                        ui.horizontal(|ui| {
+                            let card_scale = self.card_scale(key);
+                            if ui.button("A−").clicked() {
+                                *to_rescale = Some((card_idx, (card_scale - 0.1).max(0.5)));
+                            }
+                            ui.label(format!("{:.0}%", card_scale * 100.0));
+                            if ui.button("A+").clicked() {
+                                *to_rescale = Some((card_idx, (card_scale + 0.1).min(3.0)));
+                            }
+                        });
                     });
                 });
             });
@@ -774,15 +1278,37 @@ impl ApollosKiosk {
             ui.separator();
             ui.add_space(12.0);
 
+            let entry = match source {
+                CardSource::Feed(entry) => entry,
+                CardSource::Clock(clock) => {
+                    self.render_clock_card(ui, clock, scale, accent_color);
+                    return;
+                }
+            };
+
             // Card content - use the existing render_data_item logic but inline
             match &entry.content {
-                CondensedData::Gtfs(routes) => self.render_gtfs_card(ui, routes, scale),
-                CondensedData::Gbfs(stations) => self.render_gbfs_card(ui, stations, scale),
-                CondensedData::Weather(reports) => self.render_weather_card(ui, reports, scale),
-                CondensedData::Calendar(events) => self.render_calendar_card(ui, events, scale),
-                CondensedData::Aqi(reports) => self.render_aqi_card(ui, reports, scale),
-                CondensedData::Tidal(reports) => self.render_tidal_card(ui, reports, scale),
-                CondensedData::Ephem(reports) => self.render_ephem_card(ui, reports, scale),
+                CondensedData::Gtfs(routes) => {
+                    self.render_gtfs_card(ui, routes, scale, accent_color)
+                }
+                CondensedData::Gbfs(stations) => {
+                    self.render_gbfs_card(ui, stations, scale, accent_color)
+                }
+                CondensedData::Weather(reports) => {
+                    self.render_weather_card(ui, reports, scale, accent_color)
+                }
+                CondensedData::Calendar(events) => {
+                    self.render_calendar_card(ui, events, scale, accent_color)
+                }
+                CondensedData::Aqi(reports) => {
+                    self.render_aqi_card(ui, reports, scale, accent_color)
+                }
+                CondensedData::Tidal(reports) => {
+                    self.render_tidal_card(ui, reports, scale, accent_color)
+                }
+                CondensedData::Ephem(reports) => {
+                    self.render_ephem_card(ui, reports, scale, accent_color)
+                }
                 _ => {
                     ui.label(
                         egui::RichText::new("Data type not yet supported in card view").weak(),
@@ -793,36 +1319,90 @@ impl ApollosKiosk {
     }
 
     fn get_current_theme(&self) -> Theme {
+        // The theme being edited is previewed on the live layout
+        if let Some(draft) = &self.theme_editor {
+            return draft.clone();
+        }
+
+        // Overnight the night theme (if any) takes over from the selected one
+        let name = match &self.config.night_mode.theme {
+            Some(night_theme) if self.night_active => night_theme,
+            _ => &self.current_theme,
+        };
+
         self.themes
             .iter()
-            .find(|t| t.name == self.current_theme)
+            .find(|t| &t.name == name)
             .cloned()
             .unwrap_or_default()
     }
 
     fn apply_theme(&self, ctx: &egui::Context) {
         let theme = self.get_current_theme();
-        let mut visuals = egui::Visuals::dark();
-
-        visuals.panel_fill = egui::Color32::from_rgb(
-            theme.background_color[0],
-            theme.background_color[1],
-            theme.background_color[2],
-        );
-
-        visuals.extreme_bg_color = egui::Color32::from_rgb(
-            theme.background_color[0].saturating_add(10),
-            theme.background_color[1].saturating_add(10),
-            theme.background_color[2].saturating_add(10),
-        );
-
-        visuals.override_text_color = Some(egui::Color32::from_rgb(
-            theme.text_color[0],
-            theme.text_color[1],
-            theme.text_color[2],
-        ));
-
-        ctx.set_visuals(visuals);
+        let egui_theme = match theme.base() {
+            ThemeBase::Light => egui::Theme::Light,
+            ThemeBase::Dark => egui::Theme::Dark,
+        };
+
+        let family = theme.font_family(ctx);
+        ctx.set_theme(egui_theme);
+        ctx.style_mut_of(egui_theme, |style| {
+            style.visuals = theme.visuals();
+            style.text_styles = fonts::text_styles(self.config.fonts.size);
+            for (text_style, font_id) in style.text_styles.iter_mut() {
+                font_id.family = match text_style {
+                    egui::TextStyle::Monospace => egui::FontFamily::Monospace,
+                    _ => family.clone(),
+                };
+            }
+        });
+    }
+
+    /// Map a theme name from MQTT to a kiosk theme name.
+    ///
+    /// Configured aliases win over the built-in table; otherwise any known theme
+    /// matching case-insensitively is used.
+    fn resolve_theme_name(&self, name: &str) -> Option<String> {
+        let lower = name.to_lowercase();
+        let alias = self
+            .config
+            .theme_aliases
+            .get(&lower)
+            .cloned()
+            .or_else(|| theme::default_theme_aliases().remove(&lower));
+
+        let target = alias.as_deref().unwrap_or(name);
+        self.themes
+            .iter()
+            .find(|t| t.name.eq_ignore_ascii_case(target))
+            .map(|t| t.name.clone())
+    }
+
+    /// Register a theme definition pushed over MQTT, returning its name.
+    ///
+    /// Built-in and user themes are never replaced; a pushed theme with the same
+    /// name as one of those is ignored in favour of the local definition.
+    fn add_remote_theme(&mut self, theme: Theme) -> String {
+        if let Some(existing) = self
+            .themes
+            .iter()
+            .find(|t| !t.remote && t.name.eq_ignore_ascii_case(&theme.name))
+        {
+            info!(
+                target: MQTT_THEME,
+                "Ignoring definition for '{}', a local theme has that name",
+                theme.name
+            );
+            return existing.name.clone();
+        }
+
+        let name = theme.name.clone();
+        theme::save_received_theme(&self.themes_dir, &theme);
+        self.themes
+            .retain(|t| !(t.remote && t.name.eq_ignore_ascii_case(&name)));
+        self.themes.push(theme);
+        info!(target: MQTT_THEME, "Registered theme definition '{}'", name);
+        name
     }
 
     fn parse_data_entry(&self, key: &str, value: &serde_json::Value) -> Option<DataEntry> {
@@ -831,7 +1411,7 @@ impl ApollosKiosk {
             && obj.contains_key("data")
             && obj.contains_key("query")
         {
-            println!("  - Detected wrapped format for key: {}", key);
+            trace!(target: PARSE, "Detected wrapped format for {}", key);
 
             // Extract query info
             let query_info = serde_json::from_value::<QueryInfo>(obj["query"].clone()).ok()?;
@@ -840,21 +1420,23 @@ impl ApollosKiosk {
             let data_value = &obj["data"];
             let content = self.parse_content_by_prefix(key, data_value)?;
 
-            println!("  - Successfully parsed wrapped data");
+            trace!(target: PARSE, "Parsed wrapped data for {}", key);
             return Some(DataEntry {
                 content,
                 query_info: Some(query_info),
+                raw: data_value.clone(),
             });
         }
 
         // Legacy unwrapped format
-        println!("  - Parsing legacy format for key: {}", key);
+        trace!(target: PARSE, "Parsing legacy format for {}", key);
         let content = self.parse_content_by_prefix(key, value)?;
-        println!("  - Successfully parsed legacy content");
+        trace!(target: PARSE, "Parsed legacy content for {}", key);
 
         Some(DataEntry {
             content,
             query_info: None,
+            raw: value.clone(),
         })
     }
 
@@ -865,7 +1447,7 @@ impl ApollosKiosk {
     ) -> Option<CondensedData> {
         // Handle empty data (like gtfs-1 in payload)
         if value.is_null() || (value.is_object() && value.as_object()?.is_empty()) {
-            println!("  - Skipping empty data for key: {}", key);
+            debug!(target: PARSE, "Skipping empty data for {}", key);
             return None;
         }
 
@@ -873,7 +1455,7 @@ impl ApollosKiosk {
         if let Some(arr) = value.as_array()
             && arr.is_empty()
         {
-            println!("  - Skipping empty array for key: {}", key);
+            debug!(target: PARSE, "Skipping empty array for {}", key);
             return None;
         }
 
@@ -907,7 +1489,7 @@ impl ApollosKiosk {
                 .map(CondensedData::Tidal)
                 .or_else(|| {
                     if let Err(e) = serde_json::from_value::<Vec<TidalCondensed>>(value.clone()) {
-                        println!("  - Tidal parse error: {}", e);
+                        warn!(target: PARSE, "Failed to parse tidal data for {}: {}", key, e);
                     }
                     None
                 })
@@ -924,10 +1506,6 @@ impl ApollosKiosk {
         }?;
 
         // Debug success for tidal
-        if key.starts_with("tidal-") {
-            println!("  - Tidal parse result: Success");
-        }
-
         Some(content)
     }
 
@@ -935,7 +1513,9 @@ impl ApollosKiosk {
         let mut theme_changed = false;
         let mut new_theme = String::new();
         let mut show_selector = self.show_theme_selector;
-        let mut show_mqtt_config = false;
+        let mut open_editor = false;
+        let mut restart_sync = false;
+        let mut text_size_changed = false;
 
         egui::Window::new("Theme Selector")
             .open(&mut show_selector)
@@ -945,7 +1525,7 @@ impl ApollosKiosk {
                 ui.separator();
                 ui.add_space(10.0);
 
-                for theme in &self.themes {
+                for theme in self.themes.iter().filter(|t| t.path.is_none() && !t.remote) {
                     let is_selected = theme.name == self.current_theme;
                     if ui.selectable_label(is_selected, &theme.name).clicked() {
                         new_theme = theme.name.clone();
@@ -953,104 +1533,134 @@ impl ApollosKiosk {
                     }
                 }
 
+                if self.themes.iter().any(|t| t.path.is_some()) {
+                    ui.add_space(10.0);
+                    ui.label(egui::RichText::new("Custom").strong());
+                    for theme in self.themes.iter().filter(|t| t.path.is_some()) {
+                        let is_selected = theme.name == self.current_theme;
+                        if ui.selectable_label(is_selected, &theme.name).clicked() {
+                            new_theme = theme.name.clone();
+                            theme_changed = true;
+                        }
+                    }
+                }
+
+                if self.themes.iter().any(|t| t.remote) {
+                    ui.add_space(10.0);
+                    ui.label(egui::RichText::new("Synced").strong());
+                    for theme in self.themes.iter().filter(|t| t.remote) {
+                        let is_selected = theme.name == self.current_theme;
+                        if ui.selectable_label(is_selected, &theme.name).clicked() {
+                            new_theme = theme.name.clone();
+                            theme_changed = true;
+                        }
+                    }
+                }
+
+                for error in &self.theme_errors {
+                    ui.label(
+                        egui::RichText::new(format!("⚠ {}", error))
+                            .small()
+                            .color(egui::Color32::from_rgb(255, 180, 100)),
+                    );
+                }
+
+                ui.add_space(5.0);
+                if ui
+                    .add_enabled(
+                        self.theme_editor.is_none(),
+                        egui::Button::new("✏ Edit Theme..."),
+                    )
+                    .clicked()
+                {
+                    open_editor = true;
+                }
+
                 ui.add_space(10.0);
                 ui.separator();
 
-                let old_sync = self.config.mqtt_theme_sync;
                 if ui
                     .checkbox(
                         &mut self.config.mqtt_theme_sync,
-                        "Sync via MQTT (neiam/sync/theme)",
+                        format!("Sync via MQTT ({})", self.config.mqtt_theme_topic),
                     )
                     .changed()
                 {
-                    self.save_config();
-                    if self.config.mqtt_theme_sync != old_sync {
-                        ui.label(
-                            egui::RichText::new("⚠ Restart app to apply MQTT sync changes")
-                                .color(egui::Color32::from_rgb(255, 180, 100)),
-                        );
-                    }
+                    self.save_setting();
+                    restart_sync = true;
                 }
 
-                if self.config.mqtt_theme_sync && ui.button("⚙ Configure Theme MQTT...").clicked()
-                {
-                    show_mqtt_config = true;
+                if self.config.mqtt_theme_sync {
+                    self.show_theme_sync_status(ui);
+                    if ui.button("⚙ Configure Theme MQTT...").clicked() {
+                        self.settings = Some(SettingsWindow::with_tab(
+                            &self.args,
+                            &self.env_path,
+                            SettingsTab::Theme,
+                        ));
+                    }
                 }
 
-                ui.add_space(5.0);
-            });
-
-        self.show_theme_selector = show_selector;
-
-        // Theme MQTT configuration window
-        if show_mqtt_config {
-            egui::Window::new("Theme MQTT Configuration")
-                .resizable(false)
-                .collapsible(false)
-                .show(ctx, |ui| {
-                    ui.heading("Theme Sync MQTT Settings");
-                    ui.separator();
-                    ui.add_space(10.0);
-
-                    ui.horizontal(|ui| {
-                        ui.label("Host:");
-                        ui.text_edit_singleline(&mut self.config.mqtt_theme_host);
-                    });
+                ui.add_space(10.0);
+                ui.separator();
 
-                    ui.horizontal(|ui| {
-                        ui.label("Username:");
-                        let mut username =
-                            self.config.mqtt_theme_username.clone().unwrap_or_default();
-                        if ui.text_edit_singleline(&mut username).changed() {
-                            self.config.mqtt_theme_username = if username.is_empty() {
-                                None
-                            } else {
-                                Some(username)
-                            };
+                ui.label(egui::RichText::new("Display").strong());
+                ui.horizontal(|ui| {
+                    ui.label("Density:");
+                    for density in Density::ALL {
+                        if ui
+                            .selectable_label(
+                                self.config.display.density == density,
+                                density.label(),
+                            )
+                            .clicked()
+                        {
+                            self.config.display.density = density;
+                            self.save_setting();
                         }
-                    });
-
-                    ui.horizontal(|ui| {
-                        ui.label("Password:");
-                        let mut password =
-                            self.config.mqtt_theme_password.clone().unwrap_or_default();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Text size:");
+                    for size in FontSize::ALL {
                         if ui
-                            .add(egui::TextEdit::singleline(&mut password).password(true))
-                            .changed()
+                            .selectable_label(self.config.fonts.size == size, size.label())
+                            .clicked()
                         {
-                            self.config.mqtt_theme_password = if password.is_empty() {
-                                None
-                            } else {
-                                Some(password)
-                            };
+                            self.config.fonts.size = size;
+                            self.save_setting();
+                            text_size_changed = true;
                         }
-                    });
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Scale:");
+                    let response = ui.add(
+                        egui::Slider::new(&mut self.config.display.scale, 0.5..=2.0).step_by(0.05),
+                    );
+                    if response.drag_stopped() || (response.changed() && !response.dragged()) {
+                        self.save_setting();
+                    }
+                });
 
-                    ui.horizontal(|ui| {
-                        ui.label("Topic:");
-                        ui.text_edit_singleline(&mut self.config.mqtt_theme_topic);
-                    });
+                ui.add_space(5.0);
+            });
 
-                    ui.add_space(10.0);
-                    ui.separator();
+        self.show_theme_selector = show_selector;
 
-                    if ui.button("💾 Save").clicked() {
-                        self.save_config();
-                        ui.label(
-                            egui::RichText::new("⚠ Restart app to apply changes")
-                                .color(egui::Color32::from_rgb(255, 180, 100)),
-                        );
-                    }
+        if open_editor {
+            self.open_theme_editor(ctx);
+        }
 
-                    ui.add_space(5.0);
-                });
+        if restart_sync {
+            self.restart_theme_sync(ctx);
+        }
+        if text_size_changed {
+            self.apply_theme(ctx);
         }
 
         if theme_changed {
-            self.current_theme = new_theme;
-            self.apply_theme(ctx);
-            self.save_config();
+            self.select_theme(ctx, new_theme);
         }
     }
 
@@ -1070,14 +1680,19 @@ impl ApollosKiosk {
         }
     }
 
-    fn render_gtfs_card(&self, ui: &mut egui::Ui, routes: &[GtfsCondensed], scale: f32) {
-        let accent = self.get_current_theme().accent_color;
-        let accent_color = egui::Color32::from_rgb(accent[0], accent[1], accent[2]);
+    fn render_gtfs_card(
+        &self,
+        ui: &mut egui::Ui,
+        routes: &[GtfsCondensed],
+        scale: f32,
+        accent_color: egui::Color32,
+    ) {
+        let theme = self.get_current_theme();
 
         for r in routes {
             egui::Frame::NONE
                 .fill(ui.visuals().extreme_bg_color)
-                .corner_radius(8.0 * scale)
+                .corner_radius(theme.inner_corner_radius() * scale)
                 .inner_margin(12.0 * scale)
                 .show(ui, |ui| {
                     ui.set_width(ui.available_width());
@@ -1137,9 +1752,14 @@ impl ApollosKiosk {
                                 ui.horizontal(|ui| {
                                     // Icon to indicate live vs scheduled
                                     let (icon, color) = if is_live {
-                                        (ICON_RADIO, egui::Color32::from_rgb(76, 175, 80)) // Green for live
+                                        (ICON_RADIO, theme.live())
                                     } else {
-                                        (ICON_SCHEDULE, ui.visuals().weak_text_color()) // Gray for scheduled
+                                        // Scheduled times fall back to weak text
+                                        let scheduled = theme
+                                            .scheduled_color
+                                            .map(theme::rgb)
+                                            .unwrap_or_else(|| ui.visuals().weak_text_color());
+                                        (ICON_SCHEDULE, scheduled)
                                     };
 
                                     ui.label(
@@ -1160,14 +1780,19 @@ impl ApollosKiosk {
         }
     }
 
-    fn render_gbfs_card(&self, ui: &mut egui::Ui, stations: &[GbfsCondensed], scale: f32) {
-        let accent = self.get_current_theme().accent_color;
-        let accent_color = egui::Color32::from_rgb(accent[0], accent[1], accent[2]);
+    fn render_gbfs_card(
+        &self,
+        ui: &mut egui::Ui,
+        stations: &[GbfsCondensed],
+        scale: f32,
+        accent_color: egui::Color32,
+    ) {
+        let theme = self.get_current_theme();
 
         for s in stations {
             egui::Frame::NONE
                 .fill(ui.visuals().extreme_bg_color)
-                .corner_radius(8.0 * scale)
+                .corner_radius(theme.inner_corner_radius() * scale)
                 .inner_margin(12.0 * scale)
                 .show(ui, |ui| {
                     ui.set_width(ui.available_width());
@@ -1204,13 +1829,20 @@ impl ApollosKiosk {
         }
     }
 
-    fn render_weather_card(&self, ui: &mut egui::Ui, reports: &[WeatherCondensed], scale: f32) {
+    fn render_weather_card(
+        &self,
+        ui: &mut egui::Ui,
+        reports: &[WeatherCondensed],
+        scale: f32,
+        accent_color: egui::Color32,
+    ) {
         for w in reports {
             ui.horizontal(|ui| {
                 ui.label(
                     egui::RichText::new(format!("{:.0}°", w.temp))
                         .size(56.0 * scale)
-                        .strong(),
+                        .strong()
+                        .color(accent_color),
                 );
                 ui.add_space(12.0 * scale);
                 ui.vertical(|ui| {
@@ -1221,20 +1853,30 @@ impl ApollosKiosk {
                     );
                     ui.add_space(4.0 * scale);
                     ui.label(
-                        egui::RichText::new(format!("💨 {}mph  💧 {}%", w.wind.speed, w.hum))
-                            .size(14.0 * scale)
-                            .weak(),
+                        egui::RichText::new(format!(
+                            "{} {}mph  {} {}%",
+                            ICON_AIR, w.wind.speed, ICON_WATER_DROP, w.hum
+                        ))
+                        .size(14.0 * scale)
+                        .weak(),
                     );
                 });
             });
         }
     }
 
-    fn render_calendar_card(&self, ui: &mut egui::Ui, events: &[CalendarCondensed], scale: f32) {
+    fn render_calendar_card(
+        &self,
+        ui: &mut egui::Ui,
+        events: &[CalendarCondensed],
+        scale: f32,
+        accent_color: egui::Color32,
+    ) {
+        let theme = self.get_current_theme();
         for e in events {
             egui::Frame::NONE
                 .fill(ui.visuals().extreme_bg_color)
-                .corner_radius(8.0 * scale)
+                .corner_radius(theme.inner_corner_radius() * scale)
                 .inner_margin(12.0 * scale)
                 .show(ui, |ui| {
                     ui.label(
@@ -1247,14 +1889,20 @@ impl ApollosKiosk {
                         egui::RichText::new(&e.date_start)
                             .monospace()
                             .size(14.0 * scale)
-                            .weak(),
+                            .color(accent_color),
                     );
                 });
             ui.add_space(8.0 * scale);
         }
     }
 
-    fn render_aqi_card(&self, ui: &mut egui::Ui, reports: &[AqiCondensed], scale: f32) {
+    fn render_aqi_card(
+        &self,
+        ui: &mut egui::Ui,
+        reports: &[AqiCondensed],
+        scale: f32,
+        accent_color: egui::Color32,
+    ) {
         for a in reports {
             ui.label(
                 egui::RichText::new(a.name.as_deref().unwrap_or("Unknown"))
@@ -1262,32 +1910,63 @@ impl ApollosKiosk {
                     .size(16.0 * scale),
             );
             ui.add_space(4.0 * scale);
-            ui.label(
-                egui::RichText::new(format!("📊 {} measurements", a.measurements.len()))
-                    .size(14.0 * scale),
-            );
+            ui.horizontal(|ui| {
+                ui.label(
+                    egui::RichText::new(ICON_MONITORING)
+                        .size(14.0 * scale)
+                        .color(accent_color),
+                );
+                ui.label(
+                    egui::RichText::new(format!("{} measurements", a.measurements.len()))
+                        .size(14.0 * scale),
+                );
+            });
             ui.add_space(8.0 * scale);
         }
     }
 
-    fn render_tidal_card(&self, ui: &mut egui::Ui, reports: &[TidalCondensed], scale: f32) {
+    fn render_tidal_card(
+        &self,
+        ui: &mut egui::Ui,
+        reports: &[TidalCondensed],
+        scale: f32,
+        accent_color: egui::Color32,
+    ) {
         for t in reports {
-            if let Some(h) = &t.first_h {
-                ui.label(egui::RichText::new(format!("⬆ High: {}", h)).size(16.0 * scale));
-                ui.add_space(4.0 * scale);
-            }
-            if let Some(l) = &t.first_l {
-                ui.label(egui::RichText::new(format!("⬇ Low: {}", l)).size(16.0 * scale));
+            for (icon, label, time) in [
+                (ICON_NORTH, "High", &t.first_h),
+                (ICON_SOUTH, "Low", &t.first_l),
+            ] {
+                let Some(time) = time else {
+                    continue;
+                };
+                ui.horizontal(|ui| {
+                    ui.label(
+                        egui::RichText::new(icon)
+                            .size(16.0 * scale)
+                            .color(accent_color),
+                    );
+                    ui.label(
+                        egui::RichText::new(format!("{}: {}", label, time)).size(16.0 * scale),
+                    );
+                });
                 ui.add_space(4.0 * scale);
             }
         }
     }
 
-    fn render_ephem_card(&self, ui: &mut egui::Ui, reports: &[EphemerisCondensed], scale: f32) {
+    fn render_ephem_card(
+        &self,
+        ui: &mut egui::Ui,
+        reports: &[EphemerisCondensed],
+        scale: f32,
+        accent_color: egui::Color32,
+    ) {
+        let theme = self.get_current_theme();
         for ephem in reports {
             egui::Frame::NONE
                 .fill(ui.visuals().extreme_bg_color)
-                .corner_radius(8.0 * scale)
+                .corner_radius(theme.inner_corner_radius() * scale)
                 .inner_margin(12.0 * scale)
                 .show(ui, |ui| {
                     // Display the name (location/body)
@@ -1296,21 +1975,22 @@ impl ApollosKiosk {
 
                     // Display each period in a nice format
                     for (key, value) in &ephem.periods {
-                        // Choose appropriate emoji/icon based on the key
-                        let icon = if key.to_lowercase().contains("sunrise") {
-                            "🌅"
-                        } else if key.to_lowercase().contains("sunset") {
-                            "🌇"
-                        } else if key.to_lowercase().contains("moonrise") {
-                            "🌙↑"
-                        } else if key.to_lowercase().contains("moonset") {
-                            "🌙↓"
-                        } else if key.to_lowercase().contains("moon") {
-                            "🌙"
-                        } else if key.to_lowercase().contains("sun") {
-                            "☀️"
+                        // Choose appropriate icon based on the key
+                        let lower_key = key.to_lowercase();
+                        let icon = if lower_key.contains("sunrise") {
+                            format!("{}{}", ICON_WB_TWILIGHT, ICON_NORTH)
+                        } else if lower_key.contains("sunset") {
+                            format!("{}{}", ICON_WB_TWILIGHT, ICON_SOUTH)
+                        } else if lower_key.contains("moonrise") {
+                            format!("{}{}", ICON_DARK_MODE, ICON_NORTH)
+                        } else if lower_key.contains("moonset") {
+                            format!("{}{}", ICON_DARK_MODE, ICON_SOUTH)
+                        } else if lower_key.contains("moon") {
+                            ICON_DARK_MODE.to_string()
+                        } else if lower_key.contains("sun") {
+                            ICON_WB_SUNNY.to_string()
                         } else {
-                            "🔭"
+                            ICON_SCHEDULE.to_string()
                         };
 
                         // Format the key to be more readable
@@ -1330,7 +2010,11 @@ impl ApollosKiosk {
                             .join(" ");
 
                         ui.horizontal(|ui| {
-                            ui.label(egui::RichText::new(icon).size(16.0 * scale));
+                            ui.label(
+                                egui::RichText::new(icon)
+                                    .size(16.0 * scale)
+                                    .color(accent_color),
+                            );
                             ui.label(
                                 egui::RichText::new(format!("{}: ", formatted_key))
                                     .size(14.0 * scale),
@@ -1346,31 +2030,43 @@ impl ApollosKiosk {
 }
 
 fn main() -> eframe::Result {
-    // Try to load .env from XDG config directory
-    if let Some(config_dir) = dirs::config_dir() {
-        let env_path = config_dir.join("apollos-kiosk").join(".env");
-        if env_path.exists() {
-            if let Err(e) = dotenvy::from_path(&env_path) {
-                info!(
-                    "Warning: Failed to load .env from {}: {}",
-                    env_path.display(),
-                    e
-                );
-            } else {
-                info!("Loaded environment from {}", env_path.display());
-            }
-        } else {
-            println!(
-                "Did not load env, path must be created: {}",
-                env_path.display()
-            )
+    // The config location decides which .env files supply the remaining arguments
+    let paths = ConfigPaths::from_command_line();
+    let env_results: Vec<_> = paths
+        .env_files
+        .iter()
+        .filter(|path| path.exists())
+        .map(|path| (path, dotenvy::from_path(path)))
+        .collect();
+
+    let mut args = Args::parse();
+
+    let log_file = logging::log_file_path(&args);
+    logging::init(args.log_level.clone(), log_file.as_deref());
+
+    if let Err(e) = args.resolve_secrets() {
+        error!(target: CONFIG, "Failed to read password: {}", e);
+        std::process::exit(2);
+    }
+
+    for (path, result) in env_results {
+        match result {
+            Ok(()) => info!(target: CONFIG, "Loaded environment from {}", path.display()),
+            Err(e) => warn!(
+                target: CONFIG,
+                "Failed to load .env from {}: {}",
+                path.display(),
+                e
+            ),
         }
     }
 
-    let args = Args::parse();
+    let _sleep_inhibitor = args
+        .prevent_display_sleep
+        .then(window::SleepInhibitor::start);
 
     let mut options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default().with_inner_size([1920.0, 1080.0]),
+        viewport: window::viewport_builder(&args),
         ..Default::default()
     };
 
@@ -1381,6 +2077,6 @@ fn main() -> eframe::Result {
     eframe::run_native(
         "Apollos Kiosk",
         options,
-        Box::new(|cc| Ok(Box::new(ApollosKiosk::new(cc, args)))),
+        Box::new(|cc| Ok(Box::new(ApollosKiosk::new(cc, args, paths)))),
     )
 }
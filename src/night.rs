@@ -0,0 +1,193 @@
+use crate::ApollosKiosk;
+use crate::logging::DISPLAY;
+use apollos_types::CondensedData;
+use chrono::{Local, NaiveTime, Timelike};
+use eframe::egui;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct NightModeConfig {
+    #[serde(default)]
+    pub(crate) enabled: bool,
+    /// Local time night mode starts, as HH:MM
+    #[serde(default = "default_night_start")]
+    pub(crate) start: String,
+    /// Local time night mode ends, as HH:MM
+    #[serde(default = "default_night_end")]
+    pub(crate) end: String,
+    /// Ephemeris feed key whose sunset/sunrise times replace `start`/`end`
+    #[serde(default)]
+    pub(crate) ephem_key: Option<String>,
+    /// Theme to switch to overnight; keeps the current theme if unset
+    #[serde(default)]
+    pub(crate) theme: Option<String>,
+    /// Opacity of the dark overlay, from 0.0 (none) to 1.0 (black)
+    #[serde(default = "default_night_dim")]
+    pub(crate) dim: f32,
+    /// Replace the card layout with a minimal clock overnight
+    #[serde(default)]
+    pub(crate) clock_only: bool,
+}
+
+impl Default for NightModeConfig {
+    fn default() -> Self {
+        NightModeConfig {
+            enabled: false,
+            start: default_night_start(),
+            end: default_night_end(),
+            ephem_key: None,
+            theme: None,
+            dim: default_night_dim(),
+            clock_only: false,
+        }
+    }
+}
+
+fn default_night_start() -> String {
+    "22:00".to_string()
+}
+
+fn default_night_end() -> String {
+    "06:00".to_string()
+}
+
+fn default_night_dim() -> f32 {
+    0.4
+}
+
+/// Parse a time of day in one of the formats feeds and config files commonly use.
+pub(crate) fn parse_time_of_day(s: &str) -> Option<NaiveTime> {
+    let s = s.trim();
+    ["%H:%M", "%H:%M:%S", "%I:%M %p", "%I:%M:%S %p"]
+        .iter()
+        .find_map(|fmt| NaiveTime::parse_from_str(s, fmt).ok())
+        .or_else(|| {
+            chrono::DateTime::parse_from_rfc3339(s)
+                .ok()
+                .map(|dt| dt.with_timezone(&Local).time())
+        })
+}
+
+/// Whether `now` falls in the window from `start` to `end`, wrapping past midnight.
+fn in_window(now: NaiveTime, start: NaiveTime, end: NaiveTime) -> bool {
+    if start <= end {
+        now >= start && now < end
+    } else {
+        now >= start || now < end
+    }
+}
+
+impl ApollosKiosk {
+    /// Sunset and sunrise taken from the configured ephemeris feed, if available.
+    fn ephem_night_window(&self) -> Option<(NaiveTime, NaiveTime)> {
+        let key = self.config.night_mode.ephem_key.as_ref()?;
+        let CondensedData::Ephem(reports) = &self.data.get(key)?.content else {
+            return None;
+        };
+
+        // Periods are a map, so pick explicitly rather than in iteration order:
+        // the first report wins, and within it a period named exactly
+        // "sunset" over ones like "civil sunset", then by name
+        let pick = |event: &str| {
+            reports
+                .iter()
+                .enumerate()
+                .flat_map(|(idx, r)| r.periods.iter().map(move |period| (idx, period)))
+                .filter_map(|(idx, (name, value))| {
+                    let name = name.to_lowercase();
+                    let time = parse_time_of_day(value)?;
+                    name.contains(event)
+                        .then(|| ((idx, name.trim() != event, name), time))
+                })
+                .min_by(|(a, _), (b, _)| a.cmp(b))
+                .map(|(_, time)| time)
+        };
+
+        Some((pick("sunset")?, pick("sunrise")?))
+    }
+
+    fn is_night_time(&self) -> bool {
+        let night = &self.config.night_mode;
+        if !night.enabled {
+            return false;
+        }
+
+        let window = self.ephem_night_window().or_else(|| {
+            Some((
+                parse_time_of_day(&night.start)?,
+                parse_time_of_day(&night.end)?,
+            ))
+        });
+
+        match window {
+            Some((start, end)) => in_window(Local::now().time(), start, end),
+            None => false,
+        }
+    }
+
+    /// Re-evaluate the night schedule, switching theme when it changes.
+    pub(crate) fn update_night_mode(&mut self, ctx: &egui::Context) {
+        if !self.config.night_mode.enabled && !self.night_active {
+            return;
+        }
+
+        let night = self.is_night_time();
+        if night != self.night_active {
+            self.night_active = night;
+            self.apply_theme(ctx);
+            info!(
+                target: DISPLAY,
+                "Night mode {}",
+                if night { "activated" } else { "deactivated" }
+            );
+        }
+
+        // Make sure the schedule is checked even when no data is arriving
+        ctx.request_repaint_after(Duration::from_secs(30));
+    }
+
+    /// Darken everything by painting a translucent layer above the UI.
+    pub(crate) fn render_night_overlay(&self, ctx: &egui::Context) {
+        let alpha = (self.config.night_mode.dim.clamp(0.0, 1.0) * 255.0) as u8;
+        if alpha == 0 {
+            return;
+        }
+
+        let painter = ctx.layer_painter(egui::LayerId::new(
+            egui::Order::Foreground,
+            egui::Id::new("night_dim_overlay"),
+        ));
+        painter.rect_filled(
+            ctx.content_rect(),
+            0.0,
+            egui::Color32::from_black_alpha(alpha),
+        );
+    }
+
+    /// Minimal overnight page showing only the time and date.
+    pub(crate) fn render_night_clock(&self, ui: &mut egui::Ui) {
+        let now = Local::now();
+        let scale = self.get_scale_factor(ui.ctx()) * self.config.fonts.size.factor();
+
+        ui.vertical_centered(|ui| {
+            ui.add_space(ui.available_height() / 3.0);
+            ui.label(
+                egui::RichText::new(now.format("%H:%M").to_string())
+                    .size(220.0 * scale)
+                    .strong(),
+            );
+            ui.label(
+                egui::RichText::new(now.format("%A, %B %-d").to_string())
+                    .size(48.0 * scale)
+                    .weak(),
+            );
+        });
+
+        // Repaint at the start of the next minute
+        let secs_left = 60 - u64::from(now.second());
+        ui.ctx()
+            .request_repaint_after(Duration::from_secs(secs_left));
+    }
+}
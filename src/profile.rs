@@ -0,0 +1,151 @@
+use crate::Args;
+use std::path::{Path, PathBuf};
+
+/// Where one kiosk instance keeps its config and environment files.
+///
+/// Without flags this is `~/.config/apollos-kiosk/config.toml`. A named profile
+/// uses `~/.config/apollos-kiosk/profiles/<name>/config.toml`, and `--config`
+/// points at any file, with `.env` read from the same directory.
+#[derive(Debug, Clone)]
+pub(crate) struct ConfigPaths {
+    pub(crate) config: PathBuf,
+    /// `.env` files to load, most specific first
+    pub(crate) env_files: Vec<PathBuf>,
+    pub(crate) themes: PathBuf,
+    /// Exported layouts, shared between profiles
+    pub(crate) layouts: PathBuf,
+    /// Passwords entered in the settings, kept out of the config file
+    pub(crate) secrets: PathBuf,
+}
+
+/// Profile names become directory names, so keep them simple.
+pub(crate) fn parse_profile_name(s: &str) -> Result<String, String> {
+    if !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Ok(s.to_string())
+    } else {
+        Err(format!(
+            "invalid profile name '{}': use letters, digits, '-' and '_'",
+            s
+        ))
+    }
+}
+
+/// Value of `--flag value` or `--flag=value` on the command line.
+fn flag_value(args: &[String], flag: &str) -> Option<String> {
+    let prefix = format!("{}=", flag);
+    args.iter().enumerate().find_map(|(i, arg)| {
+        if arg == flag {
+            args.get(i + 1).cloned()
+        } else {
+            arg.strip_prefix(&prefix).map(str::to_string)
+        }
+    })
+}
+
+/// FNV-1a, so a name maps to the same file name or id on every run and build.
+pub(crate) fn name_hash(name: &str) -> u32 {
+    name.bytes().fold(0x811c9dc5, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x01000193)
+    })
+}
+
+/// Name telling apart kiosks on one machine: the profile, or for `--config`
+/// the file's name and a hash of its full path, since two kiosks' files may
+/// both be called config.toml.
+pub(crate) fn instance_name(config: Option<&Path>, profile: Option<&str>) -> Option<String> {
+    match (config, profile) {
+        (Some(config), _) => {
+            let config = std::fs::canonicalize(config).unwrap_or_else(|_| config.to_path_buf());
+            let stem = config
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            Some(format!(
+                "{}-{:08x}",
+                stem,
+                name_hash(&config.to_string_lossy())
+            ))
+        }
+        (None, profile) => profile.map(str::to_string),
+    }
+}
+
+/// Make MQTT client ids unique per instance (see `instance_name`) so two
+/// kiosks on one machine don't keep disconnecting each other.
+pub(crate) fn mqtt_client_id(base: &str, instance: Option<&str>) -> String {
+    match instance {
+        Some(instance) => format!("{}-{}", base, instance),
+        None => base.to_string(),
+    }
+}
+
+impl Args {
+    pub(crate) fn instance_name(&self) -> Option<String> {
+        instance_name(self.config.as_deref(), self.profile.as_deref())
+    }
+}
+
+impl ConfigPaths {
+    pub(crate) fn new(config: Option<&Path>, profile: Option<&str>) -> Self {
+        let base = dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("apollos-kiosk");
+
+        match (config, profile) {
+            (Some(config), _) => {
+                let dir = config
+                    .parent()
+                    .filter(|p| !p.as_os_str().is_empty())
+                    .unwrap_or(Path::new("."));
+                ConfigPaths {
+                    config: config.to_path_buf(),
+                    env_files: vec![dir.join(".env")],
+                    themes: dir.join("themes"),
+                    layouts: dir.join("layouts"),
+                    secrets: dir.join("secrets"),
+                }
+            }
+            (None, Some(profile)) => {
+                let dir = base.join("profiles").join(profile);
+                ConfigPaths {
+                    config: dir.join("config.toml"),
+                    // Shared settings still apply; the profile's own .env wins
+                    env_files: vec![dir.join(".env"), base.join(".env")],
+                    themes: base.join("themes"),
+                    layouts: base.join("layouts"),
+                    secrets: dir.join("secrets"),
+                }
+            }
+            (None, None) => ConfigPaths {
+                config: base.join("config.toml"),
+                env_files: vec![base.join(".env")],
+                themes: base.join("themes"),
+                layouts: base.join("layouts"),
+                secrets: base.join("secrets"),
+            },
+        }
+    }
+
+    /// Resolve paths from `--config`/`--profile` before the full argument parse,
+    /// since the `.env` files they select can supply other arguments.
+    pub(crate) fn from_command_line() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        let config = flag_value(&args, "--config")
+            .or_else(|| std::env::var("KIOSK_CONFIG").ok())
+            .map(PathBuf::from);
+        let profile = flag_value(&args, "--profile")
+            .or_else(|| std::env::var("KIOSK_PROFILE").ok())
+            .map(|p| match parse_profile_name(&p) {
+                Ok(p) => p,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(2);
+                }
+            });
+
+        Self::new(config.as_deref(), profile.as_deref())
+    }
+}
@@ -0,0 +1,538 @@
+use crate::logging::CONFIG;
+use crate::{ApollosKiosk, Config};
+use eframe::egui;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+/// Top-level config keys that are never taken from a pushed document.
+const LOCAL_ONLY_KEYS: &[&str] = &[
+    "version",
+    "config_backups",
+    "config_precedence",
+    "local_overrides",
+];
+
+/// Which side wins when a setting is both pushed and edited on the kiosk.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ConfigPrecedence {
+    /// Pushed settings replace local edits whenever they arrive
+    #[default]
+    Pushed,
+    /// Settings edited on this kiosk are kept until the edits are discarded
+    Local,
+}
+
+impl ConfigPrecedence {
+    fn label(self) -> &'static str {
+        match self {
+            ConfigPrecedence::Pushed => "Pushed config wins",
+            ConfigPrecedence::Local => "Local edits win",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ConfigSource {
+    /// The fleet-wide config topic
+    Fleet,
+    /// This kiosk's own config topic
+    Kiosk,
+}
+
+impl ConfigSource {
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            ConfigSource::Fleet => "fleet",
+            ConfigSource::Kiosk => "kiosk",
+        }
+    }
+}
+
+/// Where a setting lives in the config, e.g. `["night_mode", "theme"]`.
+type SettingPath = Vec<String>;
+
+/// Config documents received over MQTT and what they last changed.
+#[derive(Debug, Default)]
+pub(crate) struct PushedConfig {
+    fleet: Option<toml::Table>,
+    kiosk: Option<toml::Table>,
+    /// Values of pushed settings as last applied, to notice later local edits
+    applied: HashMap<SettingPath, toml::Value>,
+    /// Which document each applied setting came from
+    sources: HashMap<SettingPath, ConfigSource>,
+}
+
+/// Parse a pushed config sent as TOML or JSON; an empty payload clears it.
+fn parse_pushed_config(payload: &str) -> Result<Option<toml::Table>, String> {
+    let payload = payload.trim();
+    if payload.is_empty() {
+        Ok(None)
+    } else if payload.starts_with('{') {
+        serde_json::from_str(payload)
+            .map(Some)
+            .map_err(|e| e.to_string())
+    } else {
+        toml::from_str(payload)
+            .map(Some)
+            .map_err(|e: toml::de::Error| e.message().trim().to_string())
+    }
+}
+
+fn config_table(config: &Config) -> Result<toml::Table, String> {
+    match toml::Value::try_from(config) {
+        Ok(toml::Value::Table(table)) => Ok(table),
+        Ok(_) => Err("config is not a table".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// A setting path written the way TOML would, e.g. `night_mode.theme` or
+/// `cards."weather.home".accent`; this is how `local_overrides` names settings.
+fn path_key(path: &[String]) -> String {
+    path.iter()
+        .map(|key| {
+            let bare = !key.is_empty()
+                && key
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+            if bare {
+                key.clone()
+            } else {
+                toml::Value::String(key.clone()).to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Every setting in a pushed document, so a document can set a single field
+/// of e.g. `night_mode` without replacing the rest.
+fn settings<'a>(
+    table: &'a toml::Table,
+    prefix: &mut SettingPath,
+    out: &mut Vec<(SettingPath, &'a toml::Value)>,
+) {
+    for (key, value) in table {
+        prefix.push(key.clone());
+        match value {
+            toml::Value::Table(nested) if !nested.is_empty() => settings(nested, prefix, out),
+            _ => out.push((prefix.clone(), value)),
+        }
+        prefix.pop();
+    }
+}
+
+fn get_setting<'a>(table: &'a toml::Table, path: &[String]) -> Option<&'a toml::Value> {
+    let (last, parents) = path.split_last()?;
+    let mut table = table;
+    for key in parents {
+        table = table.get(key)?.as_table()?;
+    }
+    table.get(last)
+}
+
+fn set_setting(table: &mut toml::Table, path: &[String], value: toml::Value) {
+    let Some((last, parents)) = path.split_last() else {
+        return;
+    };
+    let mut table = table;
+    for key in parents {
+        let entry = table
+            .entry(key.clone())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        if !entry.is_table() {
+            *entry = toml::Value::Table(toml::Table::new());
+        }
+        let toml::Value::Table(nested) = entry else {
+            unreachable!("replaced with a table above");
+        };
+        table = nested;
+    }
+    table.insert(last.clone(), value);
+}
+
+/// Whether a setting, or a table holding it, was edited on this kiosk.
+/// Older configs list whole top-level keys.
+fn is_overridden(overrides: &BTreeSet<String>, path: &[String]) -> bool {
+    (1..=path.len()).any(|n| overrides.contains(&path_key(&path[..n])))
+}
+
+impl PushedConfig {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.fleet.is_none() && self.kiosk.is_none()
+    }
+
+    fn document(&self, source: ConfigSource) -> Option<&toml::Table> {
+        match source {
+            ConfigSource::Fleet => self.fleet.as_ref(),
+            ConfigSource::Kiosk => self.kiosk.as_ref(),
+        }
+    }
+
+    /// The document that sets a top-level config key, most specific first.
+    fn pushed_source(&self, key: &str) -> Option<ConfigSource> {
+        [ConfigSource::Kiosk, ConfigSource::Fleet]
+            .into_iter()
+            .find(|&source| {
+                self.document(source)
+                    .is_some_and(|doc| doc.contains_key(key))
+            })
+    }
+
+    /// The document the applied value of a top-level setting, or any field
+    /// of it, came from, most specific first.
+    fn applied_source(&self, key: &str) -> Option<ConfigSource> {
+        [ConfigSource::Kiosk, ConfigSource::Fleet]
+            .into_iter()
+            .find(|&source| {
+                self.sources
+                    .iter()
+                    .any(|(path, &from)| path[0] == key && from == source)
+            })
+    }
+
+    /// Record pushed settings whose value in `config` no longer matches what was pushed.
+    pub(crate) fn track_local_edits(&self, config: &mut Config) {
+        let Ok(table) = config_table(config) else {
+            return;
+        };
+        for (path, value) in &self.applied {
+            if get_setting(&table, path) != Some(value) {
+                config.local_overrides.insert(path_key(path));
+            }
+        }
+    }
+
+    /// Layer the fleet document and then this kiosk's over a local config.
+    pub(crate) fn layer(&mut self, local: &Config) -> Result<Config, String> {
+        let mut table = config_table(local)?;
+        self.sources.clear();
+
+        for (source, doc) in [
+            (ConfigSource::Fleet, &self.fleet),
+            (ConfigSource::Kiosk, &self.kiosk),
+        ] {
+            let Some(doc) = doc else {
+                continue;
+            };
+            let mut pushed = vec![];
+            settings(doc, &mut vec![], &mut pushed);
+            for (path, value) in pushed {
+                let kept_local = local.config_precedence == ConfigPrecedence::Local
+                    && is_overridden(&local.local_overrides, &path);
+                if LOCAL_ONLY_KEYS.contains(&path[0].as_str()) || kept_local {
+                    continue;
+                }
+                set_setting(&mut table, &path, value.clone());
+                self.sources.insert(path, source);
+            }
+        }
+
+        self.applied = self
+            .sources
+            .keys()
+            .filter_map(|path| Some((path.clone(), get_setting(&table, path)?.clone())))
+            .collect();
+
+        let mut config: Config = toml::Value::Table(table)
+            .try_into()
+            .map_err(|e: toml::de::Error| e.message().trim().to_string())?;
+        // Pushed settings replace whatever was edited locally
+        for path in self.sources.keys() {
+            for n in 1..=path.len() {
+                config.local_overrides.remove(&path_key(&path[..n]));
+            }
+        }
+        Ok(config)
+    }
+}
+
+impl ApollosKiosk {
+    /// Handle a config document received on the fleet or kiosk config topic.
+    pub(crate) fn handle_pushed_config(
+        &mut self,
+        ctx: &egui::Context,
+        source: ConfigSource,
+        payload: &str,
+    ) {
+        let document = match parse_pushed_config(payload) {
+            Ok(document) => document,
+            Err(e) => {
+                warn!(
+                    target: CONFIG,
+                    "Ignoring malformed {} config: {}",
+                    source.label(),
+                    e
+                );
+                return;
+            }
+        };
+
+        match &document {
+            Some(doc) => info!(
+                target: CONFIG,
+                "Received {} config with {} settings",
+                source.label(),
+                doc.len()
+            ),
+            None => info!(target: CONFIG, "{} config cleared", source.label()),
+        }
+        match source {
+            ConfigSource::Fleet => self.pushed_config.fleet = document,
+            ConfigSource::Kiosk => self.pushed_config.kiosk = document,
+        }
+        self.apply_pushed_config(ctx);
+    }
+
+    /// Re-apply the pushed documents over the current settings and save the result.
+    ///
+    /// A retained document arrives again on every reconnect; when it changes
+    /// nothing the file on disk is left alone and the backups aren't rotated.
+    pub(crate) fn apply_pushed_config(&mut self, ctx: &egui::Context) {
+        let mut local = self.config_snapshot();
+        self.pushed_config.track_local_edits(&mut local);
+
+        match self.pushed_config.layer(&local) {
+            Ok(config) => {
+                self.apply_config(ctx, config);
+                self.save_config();
+            }
+            Err(e) => warn!(target: CONFIG, "Failed to apply pushed config: {}", e),
+        }
+    }
+
+    /// Where the current value of a top-level setting comes from.
+    fn config_source_label(&self, key: &str) -> String {
+        let pushed = self.pushed_config.pushed_source(key);
+        let field_prefix = format!("{}.", key);
+        let edited = self
+            .config
+            .local_overrides
+            .iter()
+            .any(|path| path == key || path.starts_with(&field_prefix));
+        match (pushed, edited) {
+            (Some(source), true) => format!("this kiosk (overrides {})", source.label()),
+            (Some(_), false) => match self.pushed_config.applied_source(key) {
+                Some(source) => source.label().to_string(),
+                None => "this kiosk".to_string(),
+            },
+            (None, _) => "this kiosk".to_string(),
+        }
+    }
+
+    fn topic_status(&self, topic: Option<&String>, source: ConfigSource) -> String {
+        match (topic, self.pushed_config.document(source)) {
+            (None, _) => "not configured".to_string(),
+            (Some(topic), None) => format!("{} (nothing received)", topic),
+            (Some(topic), Some(doc)) => format!("{} ({} settings)", topic, doc.len()),
+        }
+    }
+
+    pub(crate) fn render_config_sources(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_config_sources;
+        let mut precedence = self.config.config_precedence;
+        let mut discard = false;
+        let keys: Vec<String> = config_table(&self.config_snapshot())
+            .map(|table| {
+                table
+                    .keys()
+                    .filter(|key| !LOCAL_ONLY_KEYS.contains(&key.as_str()))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        egui::Window::new("Config Sources")
+            .open(&mut open)
+            .resizable(false)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("config_topics_grid")
+                    .num_columns(2)
+                    .spacing([16.0, 6.0])
+                    .show(ui, |ui| {
+                        ui.label("Fleet topic");
+                        ui.label(self.topic_status(
+                            self.args.mqtt_fleet_config_topic.as_ref(),
+                            ConfigSource::Fleet,
+                        ));
+                        ui.end_row();
+
+                        ui.label("Kiosk topic");
+                        ui.label(self.topic_status(
+                            self.args.mqtt_kiosk_config_topic.as_ref(),
+                            ConfigSource::Kiosk,
+                        ));
+                        ui.end_row();
+
+                        ui.label("Precedence");
+                        egui::ComboBox::from_id_salt("config_precedence")
+                            .selected_text(precedence.label())
+                            .show_ui(ui, |ui| {
+                                for option in [ConfigPrecedence::Pushed, ConfigPrecedence::Local] {
+                                    ui.selectable_value(&mut precedence, option, option.label());
+                                }
+                            });
+                        ui.end_row();
+                    });
+
+                ui.add_space(8.0);
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("config_sources_grid")
+                            .num_columns(2)
+                            .spacing([16.0, 4.0])
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for key in &keys {
+                                    ui.label(key);
+                                    ui.label(self.config_source_label(key));
+                                    ui.end_row();
+                                }
+                            });
+                    });
+
+                ui.add_space(8.0);
+                let has_edits = !self.config.local_overrides.is_empty();
+                if ui
+                    .add_enabled(has_edits, egui::Button::new("Discard local edits"))
+                    .on_hover_text("Use the pushed values for every setting again")
+                    .clicked()
+                {
+                    discard = true;
+                }
+            });
+
+        if precedence != self.config.config_precedence {
+            self.config.config_precedence = precedence;
+            self.apply_pushed_config(ctx);
+        }
+        if discard {
+            self.config.local_overrides.clear();
+            // Forget what was applied so the current values don't count as edits
+            self.pushed_config.applied.clear();
+            self.apply_pushed_config(ctx);
+        }
+        self.show_config_sources = open;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pushed(fleet: Option<&str>, kiosk: Option<&str>) -> PushedConfig {
+        let parse = |doc: Option<&str>| doc.and_then(|doc| parse_pushed_config(doc).unwrap());
+        PushedConfig {
+            fleet: parse(fleet),
+            kiosk: parse(kiosk),
+            ..PushedConfig::default()
+        }
+    }
+
+    fn path(keys: &[&str]) -> SettingPath {
+        keys.iter().map(|k| k.to_string()).collect()
+    }
+
+    #[test]
+    fn path_keys_quote_like_toml() {
+        assert_eq!(
+            path_key(&path(&["night_mode", "theme"])),
+            "night_mode.theme"
+        );
+        assert_eq!(
+            path_key(&path(&["cards", "weather.home", "accent"])),
+            "cards.\"weather.home\".accent"
+        );
+    }
+
+    #[test]
+    fn kiosk_documents_win_over_fleet_ones() {
+        let mut pushed = pushed(
+            Some("current_theme = \"Light\"\nmqtt_theme_topic = \"fleet/theme\""),
+            Some(r#"{"current_theme": "Dark"}"#),
+        );
+        let config = pushed.layer(&Config::default()).unwrap();
+        assert_eq!(config.current_theme, "Dark");
+        assert_eq!(config.mqtt_theme_topic, "fleet/theme");
+        assert_eq!(
+            pushed.pushed_source("current_theme"),
+            Some(ConfigSource::Kiosk)
+        );
+        assert_eq!(
+            pushed.applied_source("mqtt_theme_topic"),
+            Some(ConfigSource::Fleet)
+        );
+    }
+
+    #[test]
+    fn nested_settings_keep_their_siblings() {
+        let local = Config {
+            night_mode: crate::night::NightModeConfig {
+                start: "21:00".to_string(),
+                ..Default::default()
+            },
+            ..Config::default()
+        };
+        let config = pushed(Some("[night_mode]\ntheme = \"Night\""), None)
+            .layer(&local)
+            .unwrap();
+        assert_eq!(config.night_mode.theme.as_deref(), Some("Night"));
+        assert_eq!(config.night_mode.start, "21:00");
+    }
+
+    #[test]
+    fn local_only_keys_are_never_pushed() {
+        let config = pushed(Some("config_backups = 99\nversion = 0"), None)
+            .layer(&Config::default())
+            .unwrap();
+        assert_eq!(config.config_backups, Config::default().config_backups);
+        assert_eq!(config.version, crate::config_file::CONFIG_VERSION);
+    }
+
+    #[test]
+    fn local_edits_are_tracked_and_replaced_when_pushed_wins() {
+        let mut pushed = pushed(Some("[night_mode]\ntheme = \"Night\""), None);
+        let mut config = pushed.layer(&Config::default()).unwrap();
+
+        pushed.track_local_edits(&mut config);
+        assert!(config.local_overrides.is_empty());
+
+        config.night_mode.theme = Some("Dark".to_string());
+        pushed.track_local_edits(&mut config);
+        assert!(config.local_overrides.contains("night_mode.theme"));
+
+        let config = pushed.layer(&config).unwrap();
+        assert_eq!(config.night_mode.theme.as_deref(), Some("Night"));
+        assert!(config.local_overrides.is_empty());
+    }
+
+    #[test]
+    fn local_edits_are_kept_when_local_wins() {
+        let mut pushed = pushed(
+            Some("current_theme = \"Light\"\n[night_mode]\ntheme = \"Night\""),
+            None,
+        );
+        let mut config = pushed.layer(&Config::default()).unwrap();
+        config.config_precedence = ConfigPrecedence::Local;
+        config.current_theme = "Solarized".to_string();
+        pushed.track_local_edits(&mut config);
+
+        let config = pushed.layer(&config).unwrap();
+        assert_eq!(config.current_theme, "Solarized");
+        assert_eq!(config.night_mode.theme.as_deref(), Some("Night"));
+        assert!(config.local_overrides.contains("current_theme"));
+        assert_eq!(pushed.applied_source("current_theme"), None);
+    }
+
+    #[test]
+    fn overriding_a_table_covers_its_fields() {
+        let overrides = BTreeSet::from(["night_mode".to_string()]);
+        assert!(is_overridden(&overrides, &path(&["night_mode", "theme"])));
+        assert!(!is_overridden(&overrides, &path(&["night", "theme"])));
+    }
+}
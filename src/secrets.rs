@@ -0,0 +1,274 @@
+use crate::config_file::backup_path;
+use crate::logging::CONFIG;
+use crate::{ApollosKiosk, Args, Config};
+use log::{info, warn};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// Secrets kept out of the config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Secret {
+    MqttPassword,
+    MqttThemePassword,
+    UnlockPin,
+}
+
+impl Secret {
+    const ALL: [Secret; 3] = [
+        Secret::MqttPassword,
+        Secret::MqttThemePassword,
+        Secret::UnlockPin,
+    ];
+
+    /// Credential name, used for systemd credentials and files in the secrets directory.
+    fn name(self) -> &'static str {
+        match self {
+            Secret::MqttPassword => "mqtt-password",
+            Secret::MqttThemePassword => "mqtt-theme-password",
+            Secret::UnlockPin => "unlock-pin",
+        }
+    }
+
+    /// Key older versions wrote the secret under in the config file.
+    fn config_key(self) -> &'static str {
+        match self {
+            Secret::MqttPassword => "mqtt_password",
+            Secret::MqttThemePassword => "mqtt_theme_password",
+            Secret::UnlockPin => "unlock_pin",
+        }
+    }
+}
+
+/// Read a secret file, dropping the trailing newline editors and `echo` add.
+fn read_secret_file(path: &Path) -> Result<String, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Ok(metadata) = fs::metadata(path)
+            && metadata.permissions().mode() & 0o077 != 0
+        {
+            warn!(
+                target: CONFIG,
+                "{} is readable by other users; consider chmod 600",
+                path.display()
+            );
+        }
+    }
+
+    Ok(content.trim_end_matches(['\n', '\r']).to_string())
+}
+
+fn read_optional(path: &Path) -> Result<Option<String>, String> {
+    match fs::metadata(path) {
+        Ok(_) => read_secret_file(path).map(Some),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+/// Find a secret, in order: a value given directly, a `--*-password-file` and a
+/// systemd credential (`LoadCredential=`).
+fn resolve(
+    name: &str,
+    value: Option<String>,
+    file: Option<&Path>,
+) -> Result<Option<String>, String> {
+    if value.is_some() {
+        return Ok(value);
+    }
+    if let Some(file) = file {
+        return read_secret_file(file).map(Some);
+    }
+    match std::env::var_os("CREDENTIALS_DIRECTORY") {
+        Some(dir) => read_optional(&PathBuf::from(dir).join(name)),
+        None => Ok(None),
+    }
+}
+
+/// A secret saved from the settings, if there is one.
+pub(crate) fn load_stored(secrets_dir: &Path, name: &str) -> Option<String> {
+    read_optional(&secrets_dir.join(name)).unwrap_or_else(|e| {
+        warn!(target: CONFIG, "Failed to read stored password: {}", e);
+        None
+    })
+}
+
+/// Keep a secret in the secrets directory, readable only by this user, or
+/// remove it when `value` is `None`.
+pub(crate) fn store(secrets_dir: &Path, name: &str, value: Option<&str>) -> Result<(), String> {
+    let path = secrets_dir.join(name);
+    let Some(value) = value else {
+        return match fs::remove_file(&path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(format!("{}: {}", path.display(), e)),
+            _ => Ok(()),
+        };
+    };
+
+    let write = || -> std::io::Result<()> {
+        fs::create_dir_all(secrets_dir)?;
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+            fs::set_permissions(secrets_dir, fs::Permissions::from_mode(0o700))?;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&path)?;
+        file.write_all(value.as_bytes())?;
+        file.sync_all()
+    };
+    write().map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+impl Config {
+    fn secret_mut(&mut self, secret: Secret) -> &mut Option<String> {
+        match secret {
+            Secret::MqttPassword => &mut self.mqtt_password,
+            Secret::MqttThemePassword => &mut self.mqtt_theme_password,
+            Secret::UnlockPin => &mut self.unlock_pin,
+        }
+    }
+
+    fn secret(&self, secret: Secret) -> Option<&String> {
+        match secret {
+            Secret::MqttPassword => self.mqtt_password.as_ref(),
+            Secret::MqttThemePassword => self.mqtt_theme_password.as_ref(),
+            Secret::UnlockPin => self.unlock_pin.as_ref(),
+        }
+    }
+
+    /// Whether the config file held any secrets, which are then moved out of it.
+    pub(crate) fn has_secrets(&self) -> bool {
+        Secret::ALL
+            .iter()
+            .any(|secret| self.secret(*secret).is_some())
+    }
+
+    /// Secrets aren't in the config file, so keep the ones in use on a reload.
+    pub(crate) fn keep_secrets(&mut self, current: &Config) {
+        for secret in Secret::ALL {
+            let value = self.secret_mut(secret);
+            if value.is_none() {
+                *value = current.secret(secret).cloned();
+            }
+        }
+    }
+
+    /// Use the secrets saved from the settings where none was given.
+    pub(crate) fn load_stored_secrets(&mut self, secrets_dir: &Path) {
+        for secret in Secret::ALL {
+            let value = self.secret_mut(secret);
+            if value.is_none() {
+                *value = load_stored(secrets_dir, secret.name());
+            }
+        }
+    }
+}
+
+/// Move passwords left in the config file by older versions, or written into
+/// it by hand, into the secrets directory; they are no longer written back to
+/// the config. A password in the file is newer than a stored one and replaces it.
+pub(crate) fn migrate_config_password(config: &Config, secrets_dir: &Path) {
+    for secret in Secret::ALL {
+        let name = secret.name();
+        let Some(password) = config.secret(secret) else {
+            continue;
+        };
+        match load_stored(secrets_dir, name) {
+            Some(stored) if &stored == password => continue,
+            Some(_) => warn!(
+                target: CONFIG,
+                "Replacing the stored {} with the one found in the config",
+                name
+            ),
+            None => {}
+        }
+        match store(secrets_dir, name, Some(password)) {
+            Ok(()) => info!(
+                target: CONFIG,
+                "Moved {} out of the config into {}",
+                name,
+                secrets_dir.display()
+            ),
+            Err(e) => warn!(target: CONFIG, "Failed to store {}: {}", name, e),
+        }
+    }
+}
+
+impl Args {
+    /// Fill in passwords and the unlock PIN that weren't given directly from
+    /// files or credentials.
+    ///
+    /// Passwords saved from the settings are left to the config, so changing
+    /// them there isn't undone by the arguments on the next reload.
+    pub(crate) fn resolve_secrets(&mut self) -> Result<(), String> {
+        self.mqtt_password = resolve(
+            Secret::MqttPassword.name(),
+            self.mqtt_password.take(),
+            self.mqtt_password_file.as_deref(),
+        )?;
+        self.mqtt_theme_password = resolve(
+            Secret::MqttThemePassword.name(),
+            self.mqtt_theme_password.take(),
+            self.mqtt_theme_password_file.as_deref(),
+        )?;
+        self.unlock_pin = resolve(Secret::UnlockPin.name(), self.unlock_pin.take(), None)?;
+        Ok(())
+    }
+}
+
+impl ApollosKiosk {
+    /// Rewrite the config and its backups without the passwords and PIN older
+    /// versions stored in them.
+    pub(crate) fn scrub_config_password(&mut self) {
+        self.save_config();
+
+        for n in 1..=self.config.config_backups {
+            let path = backup_path(&self.config_path, n);
+            let Ok(content) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let Ok(mut table) = toml::from_str::<toml::Table>(&content) else {
+                continue;
+            };
+            let removed = Secret::ALL
+                .iter()
+                .filter(|secret| table.remove(secret.config_key()).is_some())
+                .count();
+            if removed == 0 {
+                continue;
+            }
+            let result = toml::to_string_pretty(&table)
+                .map_err(|e| e.to_string())
+                .and_then(|s| fs::write(&path, s).map_err(|e| e.to_string()));
+            if let Err(e) = result {
+                warn!(
+                    target: CONFIG,
+                    "Failed to remove password from {}: {}",
+                    path.display(),
+                    e
+                );
+            }
+        }
+    }
+
+    /// Keep the passwords typed into the settings for the next start.
+    ///
+    /// Only edited ones are stored, so a password from the environment, a file
+    /// or a systemd credential is never copied into the secrets directory.
+    pub(crate) fn store_edited_secrets(&mut self) {
+        for secret in std::mem::take(&mut self.edited_secrets) {
+            let value = self.config.secret(secret).map(String::as_str);
+            if let Err(e) = store(&self.secrets_dir, secret.name(), value) {
+                warn!(target: CONFIG, "Failed to store {}: {}", secret.name(), e);
+                // Leave any earlier save error alone; this one is shown if there was none
+                self.save_error.get_or_insert(e);
+                self.edited_secrets.insert(secret);
+            }
+        }
+    }
+}
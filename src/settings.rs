@@ -0,0 +1,1220 @@
+use crate::env_file::{self, write_env_file};
+use crate::fonts::{self, FontSize};
+use crate::form::{broker_rows, fixed_field, optional_field};
+use crate::lock::NEEDS_PIN;
+use crate::logging::{self, CONFIG, LogFilter};
+use crate::night::parse_time_of_day;
+use crate::secrets::Secret;
+use crate::{ApollosKiosk, Args, Config, Density};
+use apollos_types::CondensedData;
+use eframe::egui;
+use log::{info, warn};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Pages of the settings window.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub(crate) enum SettingsTab {
+    #[default]
+    DataConnection,
+    Theme,
+    Display,
+    Layout,
+    Feeds,
+    About,
+}
+
+impl SettingsTab {
+    const ALL: [SettingsTab; 6] = [
+        SettingsTab::DataConnection,
+        SettingsTab::Theme,
+        SettingsTab::Display,
+        SettingsTab::Layout,
+        SettingsTab::Feeds,
+        SettingsTab::About,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            SettingsTab::DataConnection => "Data Connection",
+            SettingsTab::Theme => "Theme",
+            SettingsTab::Display => "Display",
+            SettingsTab::Layout => "Layout",
+            SettingsTab::Feeds => "Feeds",
+            SettingsTab::About => "About",
+        }
+    }
+}
+
+/// State of the open settings window.
+///
+/// Config settings are edited in place. Settings that only exist as arguments
+/// are edited on a copy and applied, and written to `.env`, together.
+pub(crate) struct SettingsWindow {
+    tab: SettingsTab,
+    args: Args,
+    /// Variables set in the kiosk's `.env`, which the settings can move to the config
+    env_vars: HashSet<String>,
+    log_level: String,
+    new_alias: String,
+    status: Option<String>,
+}
+
+impl SettingsWindow {
+    pub(crate) fn new(args: &Args, env_path: &Path) -> Self {
+        Self::with_tab(args, env_path, SettingsTab::default())
+    }
+
+    pub(crate) fn with_tab(args: &Args, env_path: &Path, tab: SettingsTab) -> Self {
+        SettingsWindow {
+            tab,
+            args: args.clone(),
+            env_vars: env_file::read_vars(env_path),
+            log_level: args.log_level.to_string(),
+            new_alias: String::new(),
+            status: None,
+        }
+    }
+}
+
+/// Where a card can be placed: one of the panels or the unassigned tray.
+const PLACES: [&str; 4] = [
+    "Panel 1 (Left)",
+    "Panel 2 (Center)",
+    "Panel 3 (Right)",
+    "Tray",
+];
+
+/// A change to the layout picked in the Layout tab.
+enum LayoutMove {
+    Up,
+    Down,
+    To(usize),
+}
+
+fn place_mut(config: &mut Config, place: usize) -> &mut Vec<String> {
+    match place {
+        0..3 => &mut config.panels[place],
+        _ => &mut config.unassigned,
+    }
+}
+
+fn feed_kind(content: &CondensedData) -> &'static str {
+    match content {
+        CondensedData::Gtfs(_) => "Transit",
+        CondensedData::Gbfs(_) => "Bike share",
+        CondensedData::Weather(_) => "Weather",
+        CondensedData::Calendar(_) => "Calendar",
+        CondensedData::Aqi(_) => "Air quality",
+        CondensedData::Tidal(_) => "Tides",
+        CondensedData::Ephem(_) => "Sun and moon",
+        _ => "Other",
+    }
+}
+
+/// Settings that only exist as arguments, as `.env` variables.
+fn env_settings(args: &Args) -> Vec<(&'static str, Option<String>)> {
+    let flag = |set: bool| set.then(|| "true".to_string());
+    let log_level = args.log_level.to_string();
+    let mut settings: Vec<_> = Topic::ALL
+        .into_iter()
+        .map(|topic| (topic.arg().1, topic.value(args).cloned()))
+        .collect();
+    settings.extend([
+        ("KIOSK_FULLSCREEN", flag(args.fullscreen)),
+        ("KIOSK_BORDERLESS", flag(args.borderless)),
+        (
+            "KIOSK_WINDOW_POSITION",
+            args.window_position.map(|p| format!("{},{}", p.x, p.y)),
+        ),
+        (
+            "KIOSK_HIDE_CURSOR_AFTER",
+            args.hide_cursor_after.map(|secs| secs.to_string()),
+        ),
+        ("KIOSK_PREVENT_CLOSE", flag(args.prevent_close)),
+        (
+            "KIOSK_PREVENT_DISPLAY_SLEEP",
+            flag(args.prevent_display_sleep),
+        ),
+        ("KIOSK_LOG", (log_level != "info").then_some(log_level)),
+        (
+            "KIOSK_LOG_FILE",
+            args.log_file.as_ref().map(|p| p.display().to_string()),
+        ),
+        ("KIOSK_NO_LOG_FILE", flag(args.no_log_file)),
+    ]);
+    settings
+}
+
+/// Extra topics on the data connection. They only exist as arguments, so
+/// the settings keep them in `.env`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Topic {
+    Command,
+    Alert,
+    Layout,
+    FleetConfig,
+    KioskConfig,
+}
+
+impl Topic {
+    const ALL: [Topic; 5] = [
+        Topic::Command,
+        Topic::Alert,
+        Topic::Layout,
+        Topic::FleetConfig,
+        Topic::KioskConfig,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Topic::Command => "Command topic",
+            Topic::Alert => "Alert topic",
+            Topic::Layout => "Layout topic",
+            Topic::FleetConfig => "Fleet config topic",
+            Topic::KioskConfig => "Kiosk config topic",
+        }
+    }
+
+    /// The command line flag and environment variable for the argument.
+    fn arg(self) -> (&'static str, &'static str) {
+        match self {
+            Topic::Command => ("--mqtt-command-topic", "MQTT_COMMAND_TOPIC"),
+            Topic::Alert => ("--mqtt-alert-topic", "MQTT_ALERT_TOPIC"),
+            Topic::Layout => ("--mqtt-layout-topic", "MQTT_LAYOUT_TOPIC"),
+            Topic::FleetConfig => ("--mqtt-fleet-config-topic", "MQTT_FLEET_CONFIG_TOPIC"),
+            Topic::KioskConfig => ("--mqtt-kiosk-config-topic", "MQTT_KIOSK_CONFIG_TOPIC"),
+        }
+    }
+
+    fn value(self, args: &Args) -> Option<&String> {
+        match self {
+            Topic::Command => args.mqtt_command_topic.as_ref(),
+            Topic::Alert => args.mqtt_alert_topic.as_ref(),
+            Topic::Layout => args.mqtt_layout_topic.as_ref(),
+            Topic::FleetConfig => args.mqtt_fleet_config_topic.as_ref(),
+            Topic::KioskConfig => args.mqtt_kiosk_config_topic.as_ref(),
+        }
+    }
+
+    fn value_mut(self, args: &mut Args) -> &mut Option<String> {
+        match self {
+            Topic::Command => &mut args.mqtt_command_topic,
+            Topic::Alert => &mut args.mqtt_alert_topic,
+            Topic::Layout => &mut args.mqtt_layout_topic,
+            Topic::FleetConfig => &mut args.mqtt_fleet_config_topic,
+            Topic::KioskConfig => &mut args.mqtt_kiosk_config_topic,
+        }
+    }
+}
+
+/// Config settings an argument can override.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Override {
+    MqttHost,
+    MqttUsername,
+    MqttPassword,
+    MqttTopic,
+    ThemeSync,
+    ThemeHost,
+    ThemeUsername,
+    ThemePassword,
+    ThemeTopic,
+    UnlockPin,
+    BaseResolution,
+    Scale,
+    Density,
+    FontSize,
+    Font,
+}
+
+impl Override {
+    const ALL: [Override; 15] = [
+        Override::MqttHost,
+        Override::MqttUsername,
+        Override::MqttPassword,
+        Override::MqttTopic,
+        Override::ThemeSync,
+        Override::ThemeHost,
+        Override::ThemeUsername,
+        Override::ThemePassword,
+        Override::ThemeTopic,
+        Override::UnlockPin,
+        Override::BaseResolution,
+        Override::Scale,
+        Override::Density,
+        Override::FontSize,
+        Override::Font,
+    ];
+
+    /// The command line flag and environment variable for the argument.
+    fn arg(self) -> (&'static str, &'static str) {
+        match self {
+            Override::MqttHost => ("--mqtt-host", "MQTT_HOST"),
+            Override::MqttUsername => ("--mqtt-username", "MQTT_USERNAME"),
+            Override::MqttPassword => ("--mqtt-password", "MQTT_PASSWORD"),
+            Override::MqttTopic => ("--mqtt-topic", "MQTT_TOPIC"),
+            Override::ThemeSync => ("--mqtt-theme-sync", "MQTT_THEME_SYNC"),
+            Override::ThemeHost => ("--mqtt-theme-host", "MQTT_THEME_HOST"),
+            Override::ThemeUsername => ("--mqtt-theme-username", "MQTT_THEME_USERNAME"),
+            Override::ThemePassword => ("--mqtt-theme-password", "MQTT_THEME_PASSWORD"),
+            Override::ThemeTopic => ("--mqtt-theme-topic", "MQTT_THEME_TOPIC"),
+            Override::UnlockPin => ("--unlock-pin", "KIOSK_UNLOCK_PIN"),
+            Override::BaseResolution => ("--base-resolution", "KIOSK_BASE_RESOLUTION"),
+            Override::Scale => ("--scale", "KIOSK_SCALE"),
+            Override::Density => ("--density", "KIOSK_DENSITY"),
+            Override::FontSize => ("--font-size", "KIOSK_FONT_SIZE"),
+            Override::Font => ("--font", "KIOSK_FONT"),
+        }
+    }
+
+    fn is_set(self, args: &Args) -> bool {
+        match self {
+            Override::MqttHost => args.mqtt_host.is_some(),
+            Override::MqttUsername => args.mqtt_username.is_some(),
+            Override::MqttPassword => args.mqtt_password.is_some(),
+            Override::MqttTopic => args.mqtt_topic.is_some(),
+            Override::ThemeSync => args.mqtt_theme_sync.is_some(),
+            Override::ThemeHost => args.mqtt_theme_host.is_some(),
+            Override::ThemeUsername => args.mqtt_theme_username.is_some(),
+            Override::ThemePassword => args.mqtt_theme_password.is_some(),
+            Override::ThemeTopic => args.mqtt_theme_topic.is_some(),
+            Override::UnlockPin => args.unlock_pin.is_some(),
+            Override::BaseResolution => args.base_resolution.is_some(),
+            Override::Scale => args.scale.is_some(),
+            Override::Density => args.density.is_some(),
+            Override::FontSize => args.font_size.is_some(),
+            Override::Font => args.font.is_some(),
+        }
+    }
+
+    /// Stop the argument from overriding the config for the rest of this run.
+    fn release(self, args: &mut Args) {
+        match self {
+            Override::MqttHost => args.mqtt_host = None,
+            Override::MqttUsername => args.mqtt_username = None,
+            Override::MqttPassword => args.mqtt_password = None,
+            Override::MqttTopic => args.mqtt_topic = None,
+            Override::ThemeSync => args.mqtt_theme_sync = None,
+            Override::ThemeHost => args.mqtt_theme_host = None,
+            Override::ThemeUsername => args.mqtt_theme_username = None,
+            Override::ThemePassword => args.mqtt_theme_password = None,
+            Override::ThemeTopic => args.mqtt_theme_topic = None,
+            Override::UnlockPin => args.unlock_pin = None,
+            Override::BaseResolution => args.base_resolution = None,
+            Override::Scale => args.scale = None,
+            Override::Density => args.density = None,
+            Override::FontSize => args.font_size = None,
+            Override::Font => args.font = None,
+        }
+    }
+
+    /// Secrets aren't written to the config, so they only leave `.env` once
+    /// one has been typed in and stored instead.
+    fn secret(self) -> Option<Secret> {
+        match self {
+            Override::MqttPassword => Some(Secret::MqttPassword),
+            Override::ThemePassword => Some(Secret::MqttThemePassword),
+            Override::UnlockPin => Some(Secret::UnlockPin),
+            _ => None,
+        }
+    }
+}
+
+fn on_command_line(flag: &str) -> bool {
+    let prefix = format!("{}=", flag);
+    std::env::args().any(|arg| arg == flag || arg.starts_with(&prefix))
+}
+
+/// Saving on every keystroke would rotate the config backups, so text fields
+/// are saved once editing ends.
+fn edited(response: &egui::Response) -> bool {
+    response.lost_focus() || (response.changed() && !response.has_focus())
+}
+
+/// Sliders and drag values are saved when let go, not while dragging.
+fn released(response: &egui::Response) -> bool {
+    response.drag_stopped() || (response.changed() && !response.dragged())
+}
+
+/// What sets an argument, when it is set somewhere other than the kiosk's
+/// own `.env`: the command line, the service's environment, or a password
+/// file or credential.
+fn fixed_by_arg(
+    settings: &SettingsWindow,
+    is_set: bool,
+    (flag, var): (&str, &str),
+) -> Option<String> {
+    if !is_set {
+        return None;
+    }
+    if on_command_line(flag) {
+        Some(flag.to_string())
+    } else if settings.env_vars.contains(var) {
+        None
+    } else if std::env::var_os(var).is_some() {
+        Some(var.to_string())
+    } else {
+        Some("a password file or credential".to_string())
+    }
+}
+
+fn restart_note(ui: &mut egui::Ui) {
+    ui.label(egui::RichText::new("(after restart)").weak().small());
+}
+
+impl ApollosKiosk {
+    /// Save a config setting edited in the settings window or theme selector.
+    pub(crate) fn save_setting(&mut self) {
+        self.release_env_overrides();
+        self.save_config();
+    }
+
+    /// Where the value of a setting comes from when it can't be edited here:
+    /// an argument on the command line or from the service's environment, as
+    /// opposed to the kiosk's own `.env`.
+    fn fixed_by(&self, settings: &SettingsWindow, setting: Override) -> Option<String> {
+        fixed_by_arg(settings, setting.is_set(&self.args), setting.arg())
+    }
+
+    /// Where an extra topic comes from when it can't be edited here, as for `fixed_by`.
+    fn topic_fixed_by(&self, settings: &SettingsWindow, topic: Topic) -> Option<String> {
+        fixed_by_arg(settings, topic.value(&self.args).is_some(), topic.arg())
+    }
+
+    /// Move the settings the kiosk's `.env` overrides into the config, so an
+    /// edit made here isn't undone by `.env` on the next start.
+    fn release_env_overrides(&mut self) {
+        let env_vars = env_file::read_vars(&self.env_path);
+        let mut released = Vec::new();
+        for setting in Override::ALL {
+            let (flag, var) = setting.arg();
+            let typed = setting
+                .secret()
+                .is_none_or(|secret| self.edited_secrets.contains(&secret));
+            if setting.is_set(&self.args)
+                && env_vars.contains(var)
+                && !on_command_line(flag)
+                && typed
+            {
+                setting.release(&mut self.args);
+                released.push((var, None));
+            }
+        }
+        if released.is_empty() {
+            return;
+        }
+
+        let names: Vec<&str> = released.iter().map(|(var, _)| *var).collect();
+        match write_env_file(&self.env_path, &released) {
+            Ok(()) => info!(
+                target: CONFIG,
+                "Moved {} from {} into the config",
+                names.join(", "),
+                self.env_path.display()
+            ),
+            Err(e) => {
+                warn!(
+                    target: CONFIG,
+                    "Failed to write {}: {}",
+                    self.env_path.display(),
+                    e
+                );
+                self.save_error = Some(format!("{}: {}", self.env_path.display(), e));
+            }
+        }
+    }
+
+    /// Apply the argument settings edited in the window and write them to `.env`.
+    /// Returns whether the data connection must be restarted for new topics.
+    fn apply_arg_settings(&mut self, ctx: &egui::Context, settings: &mut SettingsWindow) -> bool {
+        let log_level = match LogFilter::parse(&settings.log_level) {
+            Ok(filter) => filter,
+            Err(e) => {
+                settings.status = Some(format!("Log level: {}", e));
+                return false;
+            }
+        };
+        let draft = &settings.args;
+
+        let topics_changed = Topic::ALL
+            .into_iter()
+            .any(|topic| topic.value(&self.args) != topic.value(draft));
+        if draft.fullscreen != self.args.fullscreen {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(draft.fullscreen));
+        }
+        if draft.borderless != self.args.borderless {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Decorations(!draft.borderless));
+        }
+
+        for topic in Topic::ALL {
+            *topic.value_mut(&mut self.args) = topic.value(draft).cloned();
+        }
+        self.args.fullscreen = draft.fullscreen;
+        self.args.borderless = draft.borderless;
+        self.args.window_position = draft.window_position;
+        self.args.hide_cursor_after = draft.hide_cursor_after;
+        self.args.prevent_close = draft.prevent_close;
+        self.args.prevent_display_sleep = draft.prevent_display_sleep;
+        self.args.log_level = log_level;
+        self.args.log_file = draft.log_file.clone();
+        self.args.no_log_file = draft.no_log_file;
+        settings.args = self.args.clone();
+
+        // Topics set elsewhere would be ignored in .env, so leave them out
+        let fixed_topics: Vec<&str> = Topic::ALL
+            .into_iter()
+            .filter(|&topic| self.topic_fixed_by(settings, topic).is_some())
+            .map(|topic| topic.arg().1)
+            .collect();
+        let vars = env_settings(&self.args);
+        let vars: Vec<(&str, Option<&str>)> = vars
+            .iter()
+            .filter(|(k, _)| !fixed_topics.contains(k))
+            .map(|(k, v)| (*k, v.as_deref()))
+            .collect();
+        settings.status = Some(match write_env_file(&self.env_path, &vars) {
+            Ok(()) => {
+                info!(target: CONFIG, "Saved settings to {}", self.env_path.display());
+                // Topics saved now come from .env and stay editable
+                settings.env_vars = env_file::read_vars(&self.env_path);
+                format!("Saved to {}", self.env_path.display())
+            }
+            Err(e) => {
+                warn!(
+                    target: CONFIG,
+                    "Failed to write {}: {}",
+                    self.env_path.display(),
+                    e
+                );
+                format!("Failed to write {}: {}", self.env_path.display(), e)
+            }
+        });
+
+        topics_changed
+    }
+
+    fn render_data_connection_tab(&mut self, ui: &mut egui::Ui, settings: &mut SettingsWindow) {
+        let [host, username, password, topic] = [
+            Override::MqttHost,
+            Override::MqttUsername,
+            Override::MqttPassword,
+            Override::MqttTopic,
+        ]
+        .map(|setting| self.fixed_by(settings, setting));
+        let topics_fixed_by = Topic::ALL.map(|topic| self.topic_fixed_by(settings, topic));
+
+        egui::Grid::new("settings_data_grid")
+            .num_columns(2)
+            .spacing([16.0, 8.0])
+            .show(ui, |ui| {
+                let edit = broker_rows(
+                    ui,
+                    &mut self.config.mqtt_host,
+                    &mut self.config.mqtt_username,
+                    &mut self.config.mqtt_password,
+                    [host.as_deref(), username.as_deref(), password.as_deref()],
+                );
+                if edit.password {
+                    self.edited_secrets.insert(Secret::MqttPassword);
+                }
+
+                ui.label("Data topic");
+                fixed_field(ui, topic.as_deref(), |ui| {
+                    optional_field(ui, &mut self.config.mqtt_topic, false)
+                });
+                ui.end_row();
+
+                for (topic, fixed_by) in Topic::ALL.into_iter().zip(&topics_fixed_by) {
+                    ui.label(topic.label());
+                    fixed_field(ui, fixed_by.as_deref(), |ui| {
+                        optional_field(ui, topic.value_mut(&mut settings.args), false)
+                    });
+                    ui.end_row();
+                }
+
+                ui.label("Status");
+                match &self.data_connection {
+                    Some(connection) => connection.status().show(ui),
+                    None => {
+                        ui.label(egui::RichText::new("Not configured").weak());
+                    }
+                }
+                ui.end_row();
+            });
+
+        ui.add_space(8.0);
+        if ui.button("💾 Save and Reconnect").clicked() {
+            self.save_setting();
+            self.store_edited_secrets();
+            self.apply_arg_settings(ui.ctx(), settings);
+            // Connection settings may have changed even if the topics didn't
+            self.restart_data_connection(ui.ctx());
+        }
+    }
+
+    fn render_theme_tab(&mut self, ui: &mut egui::Ui, settings: &mut SettingsWindow) {
+        let mut selected = None;
+        let mut restart_sync = false;
+
+        ui.horizontal(|ui| {
+            ui.label("Theme");
+            egui::ComboBox::from_id_salt("settings_theme")
+                .selected_text(&self.current_theme)
+                .show_ui(ui, |ui| {
+                    for theme in &self.themes {
+                        if ui
+                            .selectable_label(theme.name == self.current_theme, &theme.name)
+                            .clicked()
+                        {
+                            selected = Some(theme.name.clone());
+                        }
+                    }
+                });
+            if ui
+                .add_enabled(self.theme_editor.is_none(), egui::Button::new("✏ Edit..."))
+                .clicked()
+            {
+                self.open_theme_editor(ui.ctx());
+            }
+        });
+
+        let [sync, host, username, password, topic] = [
+            Override::ThemeSync,
+            Override::ThemeHost,
+            Override::ThemeUsername,
+            Override::ThemePassword,
+            Override::ThemeTopic,
+        ]
+        .map(|setting| self.fixed_by(settings, setting));
+
+        ui.add_space(8.0);
+        ui.label(egui::RichText::new("Theme sync").strong());
+        if fixed_field(ui, sync.as_deref(), |ui| {
+            ui.checkbox(&mut self.config.mqtt_theme_sync, "Sync theme via MQTT")
+                .changed()
+        }) {
+            self.save_setting();
+            restart_sync = true;
+        }
+        egui::Grid::new("settings_theme_sync_grid")
+            .num_columns(2)
+            .spacing([16.0, 8.0])
+            .show(ui, |ui| {
+                let edit = broker_rows(
+                    ui,
+                    &mut self.config.mqtt_theme_host,
+                    &mut self.config.mqtt_theme_username,
+                    &mut self.config.mqtt_theme_password,
+                    [host.as_deref(), username.as_deref(), password.as_deref()],
+                );
+                if edit.password {
+                    self.edited_secrets.insert(Secret::MqttThemePassword);
+                }
+
+                ui.label("Topic");
+                fixed_field(ui, topic.as_deref(), |ui| {
+                    ui.text_edit_singleline(&mut self.config.mqtt_theme_topic)
+                });
+                ui.end_row();
+
+                ui.label("Status");
+                self.show_theme_sync_status(ui);
+                ui.end_row();
+            });
+        if ui.button("💾 Save and Reconnect").clicked() {
+            self.save_setting();
+            self.store_edited_secrets();
+            restart_sync = true;
+        }
+
+        ui.add_space(8.0);
+        ui.label(egui::RichText::new("Theme names from other apps").strong());
+        let mut aliases: Vec<(String, String)> = self
+            .config
+            .theme_aliases
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        aliases.sort();
+        let mut remove = None;
+        let mut retarget = None;
+        egui::Grid::new("settings_aliases_grid")
+            .num_columns(3)
+            .spacing([12.0, 6.0])
+            .show(ui, |ui| {
+                for (alias, target) in &aliases {
+                    ui.label(alias);
+                    egui::ComboBox::from_id_salt(("settings_alias", alias))
+                        .selected_text(target)
+                        .show_ui(ui, |ui| {
+                            for theme in &self.themes {
+                                if ui
+                                    .selectable_label(&theme.name == target, &theme.name)
+                                    .clicked()
+                                {
+                                    retarget = Some((alias.clone(), theme.name.clone()));
+                                }
+                            }
+                        });
+                    if ui.small_button("🗑").clicked() {
+                        remove = Some(alias.clone());
+                    }
+                    ui.end_row();
+                }
+            });
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut settings.new_alias)
+                    .hint_text("Name sent by the other app"),
+            );
+            let alias = settings.new_alias.trim().to_lowercase();
+            if ui
+                .add_enabled(!alias.is_empty(), egui::Button::new("Add"))
+                .clicked()
+            {
+                retarget = Some((alias, self.current_theme.clone()));
+                settings.new_alias.clear();
+            }
+        });
+
+        if let Some(alias) = remove {
+            self.config.theme_aliases.remove(&alias);
+            self.save_setting();
+        }
+        if let Some((alias, target)) = retarget {
+            self.config.theme_aliases.insert(alias, target);
+            self.save_setting();
+        }
+        if restart_sync {
+            self.restart_theme_sync(ui.ctx());
+        }
+        if let Some(name) = selected {
+            self.select_theme(ui.ctx(), name);
+        }
+    }
+
+    fn render_display_tab(&mut self, ui: &mut egui::Ui, settings: &mut SettingsWindow) {
+        let mut save = false;
+        let mut restyle = false;
+        let mut reload_fonts = false;
+        let [resolution, scale, density_arg, font_size, font_arg, pin] = [
+            Override::BaseResolution,
+            Override::Scale,
+            Override::Density,
+            Override::FontSize,
+            Override::Font,
+            Override::UnlockPin,
+        ]
+        .map(|setting| self.fixed_by(settings, setting));
+
+        ui.label(egui::RichText::new("Scaling").strong());
+        egui::Grid::new("settings_scaling_grid")
+            .num_columns(2)
+            .spacing([16.0, 8.0])
+            .show(ui, |ui| {
+                let display = &mut self.config.display;
+                ui.label("Designed for");
+                fixed_field(ui, resolution.as_deref(), |ui| {
+                    let width =
+                        ui.add(egui::DragValue::new(&mut display.base_width).range(320.0..=7680.0));
+                    ui.label("×");
+                    let height = ui
+                        .add(egui::DragValue::new(&mut display.base_height).range(240.0..=4320.0));
+                    save |= released(&width) || released(&height);
+                });
+                ui.end_row();
+
+                ui.label("Scale");
+                fixed_field(ui, scale.as_deref(), |ui| {
+                    let response =
+                        ui.add(egui::Slider::new(&mut display.scale, 0.5..=2.0).step_by(0.05));
+                    save |= released(&response);
+                });
+                ui.end_row();
+
+                ui.label("Density");
+                fixed_field(ui, density_arg.as_deref(), |ui| {
+                    for density in Density::ALL {
+                        if ui
+                            .selectable_label(display.density == density, density.label())
+                            .clicked()
+                        {
+                            display.density = density;
+                            save = true;
+                        }
+                    }
+                });
+                ui.end_row();
+
+                ui.label("Text size");
+                fixed_field(ui, font_size.as_deref(), |ui| {
+                    for size in FontSize::ALL {
+                        if ui
+                            .selectable_label(self.config.fonts.size == size, size.label())
+                            .clicked()
+                        {
+                            self.config.fonts.size = size;
+                            save = true;
+                            restyle = true;
+                        }
+                    }
+                });
+                ui.end_row();
+
+                ui.label("Font");
+                fixed_field(ui, font_arg.as_deref(), |ui| {
+                    let mut font = self.config.fonts.proportional.clone();
+                    let response = ui.add(
+                        egui::TextEdit::singleline(font.get_or_insert_default())
+                            .hint_text("\"bundled\" or a font file"),
+                    );
+                    if response.changed() {
+                        self.config.fonts.proportional = font.filter(|f| !f.trim().is_empty());
+                    }
+                    reload_fonts |= edited(&response);
+                });
+                ui.end_row();
+
+                ui.label("Monospace font");
+                let mut font = self.config.fonts.monospace.clone();
+                let response = ui.add(
+                    egui::TextEdit::singleline(font.get_or_insert_default()).hint_text("Font file"),
+                );
+                if response.changed() {
+                    self.config.fonts.monospace = font.filter(|f| !f.trim().is_empty());
+                }
+                reload_fonts |= edited(&response);
+                ui.end_row();
+            });
+
+        ui.add_space(8.0);
+        ui.label(egui::RichText::new("Night mode").strong());
+        let ephem_keys: Vec<String> = {
+            let mut keys: Vec<String> = self
+                .data
+                .iter()
+                .filter(|(_, entry)| matches!(entry.content, CondensedData::Ephem(_)))
+                .map(|(key, _)| key.clone())
+                .collect();
+            keys.sort();
+            keys
+        };
+        let theme_names: Vec<String> = self.themes.iter().map(|t| t.name.clone()).collect();
+        egui::Grid::new("settings_night_grid")
+            .num_columns(2)
+            .spacing([16.0, 8.0])
+            .show(ui, |ui| {
+                let night = &mut self.config.night_mode;
+                ui.label("Enabled");
+                save |= ui.checkbox(&mut night.enabled, "").changed();
+                ui.end_row();
+
+                for (label, time) in [("Starts", &mut night.start), ("Ends", &mut night.end)] {
+                    ui.label(label);
+                    ui.horizontal(|ui| {
+                        let response = ui.add(egui::TextEdit::singleline(time).desired_width(80.0));
+                        save |= edited(&response);
+                        if parse_time_of_day(time).is_none() {
+                            ui.colored_label(egui::Color32::from_rgb(255, 180, 100), "⚠ HH:MM");
+                        }
+                    });
+                    ui.end_row();
+                }
+
+                ui.label("Follow sunset from");
+                egui::ComboBox::from_id_salt("settings_night_ephem")
+                    .selected_text(night.ephem_key.as_deref().unwrap_or("Fixed times"))
+                    .show_ui(ui, |ui| {
+                        save |= ui
+                            .selectable_value(&mut night.ephem_key, None, "Fixed times")
+                            .clicked();
+                        for key in &ephem_keys {
+                            save |= ui
+                                .selectable_value(&mut night.ephem_key, Some(key.clone()), key)
+                                .clicked();
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Night theme");
+                egui::ComboBox::from_id_salt("settings_night_theme")
+                    .selected_text(night.theme.as_deref().unwrap_or("Keep current"))
+                    .show_ui(ui, |ui| {
+                        restyle |= ui
+                            .selectable_value(&mut night.theme, None, "Keep current")
+                            .clicked();
+                        for name in &theme_names {
+                            restyle |= ui
+                                .selectable_value(&mut night.theme, Some(name.clone()), name)
+                                .clicked();
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Dim");
+                save |= released(&ui.add(egui::Slider::new(&mut night.dim, 0.0..=1.0)));
+                ui.end_row();
+
+                ui.label("Clock only");
+                save |= ui.checkbox(&mut night.clock_only, "").changed();
+                ui.end_row();
+            });
+
+        ui.add_space(8.0);
+        ui.label(egui::RichText::new("Lock").strong());
+        ui.horizontal(|ui| {
+            ui.label("Unlock PIN");
+            let pin_edited = fixed_field(ui, pin.as_deref(), |ui| {
+                let response = ui.add(
+                    egui::TextEdit::singleline(self.config.unlock_pin.get_or_insert_default())
+                        .password(true)
+                        .desired_width(100.0),
+                );
+                if self.config.unlock_pin.as_deref() == Some("") {
+                    self.config.unlock_pin = None;
+                }
+                edited(&response)
+            });
+            // Stored with the passwords rather than in the config file
+            if pin_edited {
+                self.edited_secrets.insert(Secret::UnlockPin);
+                self.save_setting();
+                self.store_edited_secrets();
+            }
+            if ui
+                .add_enabled(self.config.can_lock(), egui::Button::new("🔒 Lock now"))
+                .on_disabled_hover_text(NEEDS_PIN)
+                .clicked()
+            {
+                self.set_locked(true);
+            }
+        });
+
+        ui.add_space(8.0);
+        ui.label(egui::RichText::new("Window").strong());
+        let draft = &mut settings.args;
+        ui.checkbox(&mut draft.fullscreen, "Fullscreen");
+        ui.checkbox(&mut draft.borderless, "Hide window borders");
+        ui.checkbox(&mut draft.prevent_close, "Ignore close requests");
+        ui.horizontal(|ui| {
+            let mut hide = draft.hide_cursor_after.is_some();
+            if ui.checkbox(&mut hide, "Hide cursor after").changed() {
+                draft.hide_cursor_after = hide.then_some(5);
+            }
+            if let Some(secs) = &mut draft.hide_cursor_after {
+                ui.add(egui::DragValue::new(secs).range(1..=3600).suffix(" s"));
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut draft.prevent_display_sleep, "Keep the display awake");
+            restart_note(ui);
+        });
+        ui.horizontal(|ui| {
+            let mut place = draft.window_position.is_some();
+            if ui.checkbox(&mut place, "Open at").changed() {
+                draft.window_position = place.then_some(egui::Pos2::ZERO);
+            }
+            if let Some(position) = &mut draft.window_position {
+                ui.add(egui::DragValue::new(&mut position.x).prefix("x "));
+                ui.add(egui::DragValue::new(&mut position.y).prefix("y "));
+            }
+            restart_note(ui);
+        });
+        if ui.button("Apply").clicked() && self.apply_arg_settings(ui.ctx(), settings) {
+            self.restart_data_connection(ui.ctx());
+        }
+
+        if reload_fonts {
+            fonts::install_fonts(ui.ctx(), &self.config.fonts);
+            restyle = true;
+        }
+        if save || restyle || reload_fonts {
+            self.save_setting();
+        }
+        if restyle {
+            self.apply_theme(ui.ctx());
+        }
+    }
+
+    fn card_label(&self, key: &str) -> String {
+        self.card_override(key)
+            .and_then(|o| o.title.clone())
+            .unwrap_or_else(|| key.to_string())
+    }
+
+    fn render_layout_tab(&mut self, ui: &mut egui::Ui) {
+        let mut change = None;
+
+        for (place, name) in PLACES.iter().enumerate() {
+            let cards = place_mut(&mut self.config, place).clone();
+            ui.label(egui::RichText::new(*name).strong());
+            if cards.is_empty() {
+                ui.label(egui::RichText::new("Empty").weak());
+            }
+            for (idx, key) in cards.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(idx > 0, egui::Button::new("⬆").small())
+                        .clicked()
+                    {
+                        change = Some((place, idx, LayoutMove::Up));
+                    }
+                    if ui
+                        .add_enabled(idx + 1 < cards.len(), egui::Button::new("⬇").small())
+                        .clicked()
+                    {
+                        change = Some((place, idx, LayoutMove::Down));
+                    }
+                    egui::ComboBox::from_id_salt(("settings_move", key))
+                        .selected_text(PLACES[place])
+                        .width(130.0)
+                        .show_ui(ui, |ui| {
+                            for (to, to_name) in PLACES.iter().enumerate() {
+                                if to != place && ui.selectable_label(false, *to_name).clicked() {
+                                    change = Some((place, idx, LayoutMove::To(to)));
+                                }
+                            }
+                        });
+                    ui.label(self.card_label(key));
+                });
+            }
+            ui.add_space(6.0);
+        }
+
+        if let Some((place, idx, action)) = change {
+            let from = place_mut(&mut self.config, place);
+            match action {
+                LayoutMove::Up => from.swap(idx, idx - 1),
+                LayoutMove::Down => from.swap(idx, idx + 1),
+                LayoutMove::To(to) => {
+                    let key = from.remove(idx);
+                    place_mut(&mut self.config, to).push(key);
+                }
+            }
+            self.save_setting();
+        }
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("Config backups kept");
+            let response =
+                ui.add(egui::DragValue::new(&mut self.config.config_backups).range(0..=20));
+            if released(&response) {
+                self.save_setting();
+            }
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Import or export layouts...").clicked() {
+                self.show_layouts = true;
+            }
+            let has_config_topics = self.args.mqtt_fleet_config_topic.is_some()
+                || self.args.mqtt_kiosk_config_topic.is_some();
+            if has_config_topics && ui.button("Config sources...").clicked() {
+                self.show_config_sources = true;
+            }
+        });
+        ui.label(
+            egui::RichText::new(format!(
+                "Clocks and highlight rules are edited in {}.",
+                self.config_path.display()
+            ))
+            .weak(),
+        );
+    }
+
+    fn render_feeds_tab(&mut self, ui: &mut egui::Ui) {
+        let mut keys: Vec<String> = self.data.keys().cloned().collect();
+        keys.sort();
+        if keys.is_empty() {
+            ui.label(egui::RichText::new("No feeds received yet").weak());
+            return;
+        }
+
+        let mut rescale = None;
+        let mut edit = None;
+        egui::Grid::new("settings_feeds_grid")
+            .num_columns(5)
+            .spacing([16.0, 6.0])
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label(egui::RichText::new("Card").strong());
+                ui.label(egui::RichText::new("Type").strong());
+                ui.label(egui::RichText::new("Placed on").strong());
+                ui.label(egui::RichText::new("Text size").strong());
+                ui.end_row();
+
+                for key in &keys {
+                    let placed = self
+                        .config
+                        .panels
+                        .iter()
+                        .position(|p| p.contains(key))
+                        .map(|idx| PLACES[idx])
+                        .unwrap_or(PLACES[3]);
+                    ui.label(self.card_label(key)).on_hover_text(key);
+                    ui.label(feed_kind(&self.data[key].content));
+                    ui.label(placed);
+                    ui.horizontal(|ui| {
+                        let scale = self.card_scale(key);
+                        if ui.small_button("−").clicked() {
+                            rescale = Some((key.clone(), (scale - 0.1).max(0.5)));
+                        }
+                        ui.label(format!("{:.0}%", scale * 100.0));
+                        if ui.small_button("+").clicked() {
+                            rescale = Some((key.clone(), (scale + 0.1).min(3.0)));
+                        }
+                    });
+                    if ui.button("✏ Edit card...").clicked() {
+                        edit = Some(key.clone());
+                    }
+                    ui.end_row();
+                }
+            });
+
+        if let Some((key, scale)) = rescale {
+            if (scale - 1.0).abs() < f32::EPSILON {
+                self.config.display.card_scale.remove(&key);
+            } else {
+                self.config.display.card_scale.insert(key, scale);
+            }
+            self.save_setting();
+        }
+        if let Some(key) = edit {
+            self.open_card_editor(key);
+        }
+    }
+
+    fn render_about_tab(&mut self, ui: &mut egui::Ui, settings: &mut SettingsWindow) {
+        let log_file = logging::log_file_path(&self.args);
+        let path_label = |path: Option<&std::path::Path>| {
+            path.map(|p| p.display().to_string())
+                .unwrap_or_else(|| "none".to_string())
+        };
+
+        egui::Grid::new("settings_about_grid")
+            .num_columns(2)
+            .spacing([16.0, 6.0])
+            .show(ui, |ui| {
+                ui.label("Version");
+                ui.label(env!("CARGO_PKG_VERSION"));
+                ui.end_row();
+
+                ui.label("Profile");
+                ui.label(self.args.profile.as_deref().unwrap_or("default"));
+                ui.end_row();
+
+                ui.label("Kiosk id");
+                ui.label(&self.theme_sync_id);
+                ui.end_row();
+
+                ui.label("Data feeds");
+                ui.label(self.data.len().to_string());
+                ui.end_row();
+
+                for (label, path) in [
+                    ("Config", Some(self.config_path.as_path())),
+                    ("Environment", Some(self.env_path.as_path())),
+                    ("Themes", Some(self.themes_dir.as_path())),
+                    ("Layouts", Some(self.layouts_dir.as_path())),
+                    ("Passwords", Some(self.secrets_dir.as_path())),
+                    ("Log file", log_file.as_deref()),
+                ] {
+                    ui.label(label);
+                    ui.label(path_label(path));
+                    ui.end_row();
+                }
+            });
+
+        ui.add_space(8.0);
+        ui.label(egui::RichText::new("Logging").strong());
+        let draft = &mut settings.args;
+        egui::Grid::new("settings_logging_grid")
+            .num_columns(2)
+            .spacing([16.0, 8.0])
+            .show(ui, |ui| {
+                ui.label("Log levels");
+                ui.add(
+                    egui::TextEdit::singleline(&mut settings.log_level)
+                        .hint_text("warn,mqtt-data=debug"),
+                );
+                ui.end_row();
+
+                ui.label("Log file");
+                let mut path = draft
+                    .log_file
+                    .as_ref()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_default();
+                if ui
+                    .add(egui::TextEdit::singleline(&mut path).hint_text("Default location"))
+                    .changed()
+                {
+                    draft.log_file = (!path.trim().is_empty()).then(|| path.into());
+                }
+                ui.end_row();
+
+                ui.label("");
+                ui.checkbox(&mut draft.no_log_file, "Only log to stderr");
+                ui.end_row();
+            });
+        ui.horizontal(|ui| {
+            if ui.button("Apply").clicked() && self.apply_arg_settings(ui.ctx(), settings) {
+                self.restart_data_connection(ui.ctx());
+            }
+            restart_note(ui);
+        });
+    }
+
+    pub(crate) fn render_settings_window(&mut self, ctx: &egui::Context) {
+        let Some(mut settings) = self.settings.take() else {
+            return;
+        };
+        let mut open = true;
+
+        egui::Window::new("Settings")
+            .open(&mut open)
+            .resizable(false)
+            .collapsible(false)
+            .default_width(560.0)
+            .show(ctx, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    for tab in SettingsTab::ALL {
+                        ui.selectable_value(&mut settings.tab, tab, tab.label());
+                    }
+                });
+                ui.separator();
+
+                egui::ScrollArea::vertical()
+                    .max_height(520.0)
+                    .show(ui, |ui| match settings.tab {
+                        SettingsTab::DataConnection => {
+                            self.render_data_connection_tab(ui, &mut settings)
+                        }
+                        SettingsTab::Theme => self.render_theme_tab(ui, &mut settings),
+                        SettingsTab::Display => self.render_display_tab(ui, &mut settings),
+                        SettingsTab::Layout => self.render_layout_tab(ui),
+                        SettingsTab::Feeds => self.render_feeds_tab(ui),
+                        SettingsTab::About => self.render_about_tab(ui, &mut settings),
+                    });
+
+                if let Some(status) = &settings.status {
+                    ui.separator();
+                    ui.label(status);
+                }
+                ui.label(
+                    egui::RichText::new(
+                        "Settings marked \"set by\" come from the command line or the service's \
+                         environment and can only be changed there.",
+                    )
+                    .weak()
+                    .small(),
+                );
+            });
+
+        // Locking from the Display tab closes the window
+        if open && !self.config.locked {
+            self.settings = Some(settings);
+        }
+    }
+}
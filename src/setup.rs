@@ -0,0 +1,416 @@
+use crate::data_mqtt::broker_uri;
+use crate::env_file::write_env_file;
+use crate::form::{broker_rows, optional_field};
+use crate::logging::{CONFIG, MQTT_DATA};
+use crate::secrets::Secret;
+use crate::theme_sync::sync_source_id;
+use crate::{ApollosKiosk, Args, Config};
+use eframe::egui;
+use log::{info, warn};
+use paho_mqtt as mqtt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Pages of the first-run setup, in order.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub(crate) enum SetupStep {
+    #[default]
+    Broker,
+    Topics,
+    Theme,
+    Finish,
+}
+
+impl SetupStep {
+    const ALL: [SetupStep; 4] = [
+        SetupStep::Broker,
+        SetupStep::Topics,
+        SetupStep::Theme,
+        SetupStep::Finish,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            SetupStep::Broker => "Broker",
+            SetupStep::Topics => "Topics",
+            SetupStep::Theme => "Theme",
+            SetupStep::Finish => "Finish",
+        }
+    }
+
+    fn index(self) -> usize {
+        Self::ALL.iter().position(|&s| s == self).unwrap_or(0)
+    }
+}
+
+/// Result of a connection test, `None` while it is still running.
+type TestResult = Arc<Mutex<Option<Result<String, String>>>>;
+
+/// State of the setup wizard shown until a data topic is configured.
+///
+/// Broker settings are edited in the config directly; topics that only exist
+/// as arguments are kept here until they're written to `.env`.
+#[derive(Default)]
+pub(crate) struct SetupWizard {
+    step: SetupStep,
+    command_topic: Option<String>,
+    alert_topic: Option<String>,
+    test: Option<TestResult>,
+}
+
+impl SetupWizard {
+    pub(crate) fn new(args: &Args) -> Self {
+        SetupWizard {
+            command_topic: args.mqtt_command_topic.clone(),
+            alert_topic: args.mqtt_alert_topic.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Connect to the data broker once with the entered settings, subscribing to
+/// the data topic if there is one, and report how it went.
+fn test_connection(config: &Config, ctx: egui::Context) -> TestResult {
+    let result: TestResult = Arc::new(Mutex::new(None));
+    let uri = broker_uri(&config.mqtt_host);
+    let username = config.mqtt_username.clone();
+    let password = config.mqtt_password.clone();
+    let topic = config.mqtt_topic.clone();
+
+    let thread_result = result.clone();
+    std::thread::spawn(move || {
+        let test = || -> Result<String, String> {
+            let create_opts = mqtt::CreateOptionsBuilder::new()
+                .server_uri(uri.clone())
+                .client_id(format!("{}-setup", sync_source_id()))
+                .finalize();
+            let cli = mqtt::Client::new(create_opts).map_err(|e| e.to_string())?;
+
+            let mut conn_opts = mqtt::ConnectOptionsBuilder::new();
+            conn_opts
+                .connect_timeout(Duration::from_secs(5))
+                .clean_session(true);
+            if let Some(username) = &username {
+                conn_opts.user_name(username);
+            }
+            if let Some(password) = &password {
+                conn_opts.password(password);
+            }
+            cli.connect(conn_opts.finalize())
+                .map_err(|e| e.to_string())?;
+
+            let subscribed = topic
+                .as_ref()
+                .map(|topic| {
+                    cli.subscribe(topic, 1).map_err(|e| {
+                        format!("connected, but can't subscribe to '{}': {}", topic, e)
+                    })
+                })
+                .transpose();
+            let _ = cli.disconnect(None);
+            subscribed?;
+
+            Ok(match &topic {
+                Some(topic) => format!("Connected to {} and subscribed to {}", uri, topic),
+                None => format!("Connected to {}", uri),
+            })
+        };
+
+        let outcome = test();
+        match &outcome {
+            Ok(message) => info!(target: MQTT_DATA, "Setup test: {}", message),
+            Err(e) => warn!(target: MQTT_DATA, "Setup test failed: {}", e),
+        }
+        *thread_result.lock().unwrap() = Some(outcome);
+        ctx.request_repaint();
+    });
+    result
+}
+
+impl ApollosKiosk {
+    fn render_setup_broker(&mut self, ui: &mut egui::Ui) {
+        ui.label(
+            egui::RichText::new(
+                "A host name like \"broker.local\", or a URI like \"ssl://broker:8883\". \
+                 Leave the username and password empty for anonymous brokers.",
+            )
+            .weak(),
+        );
+        ui.add_space(8.0);
+
+        let edit = egui::Grid::new("setup_broker_grid")
+            .num_columns(2)
+            .spacing([16.0, 8.0])
+            .show(ui, |ui| {
+                broker_rows(
+                    ui,
+                    &mut self.config.mqtt_host,
+                    &mut self.config.mqtt_username,
+                    &mut self.config.mqtt_password,
+                    [None; 3],
+                )
+            })
+            .inner;
+        if edit.password {
+            self.edited_secrets.insert(Secret::MqttPassword);
+        }
+        if edit.changed {
+            // An earlier result no longer says anything about these settings
+            self.setup.test = None;
+        }
+
+        ui.add_space(8.0);
+        self.render_connection_test(ui);
+    }
+
+    fn render_connection_test(&mut self, ui: &mut egui::Ui) {
+        let result = self
+            .setup
+            .test
+            .as_ref()
+            .map(|test| test.lock().unwrap().clone());
+        let running = matches!(result, Some(None));
+
+        if ui
+            .add_enabled(!running, egui::Button::new("Test connection"))
+            .clicked()
+        {
+            self.setup.test = Some(test_connection(&self.config, ui.ctx().clone()));
+        }
+        match result {
+            Some(None) => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Connecting...");
+                });
+            }
+            Some(Some(Ok(message))) => {
+                ui.colored_label(egui::Color32::from_rgb(76, 175, 80), message);
+            }
+            Some(Some(Err(e))) => {
+                ui.colored_label(
+                    egui::Color32::from_rgb(255, 100, 100),
+                    format!("Failed: {}", e),
+                );
+            }
+            None => {}
+        }
+    }
+
+    fn render_setup_topics(&mut self, ui: &mut egui::Ui) {
+        ui.label(
+            egui::RichText::new(
+                "Feeds arrive as JSON maps on the data topic. Commands and alerts \
+                 are optional.",
+            )
+            .weak(),
+        );
+        ui.add_space(8.0);
+
+        let mut changed = false;
+        egui::Grid::new("setup_topics_grid")
+            .num_columns(2)
+            .spacing([16.0, 8.0])
+            .show(ui, |ui| {
+                ui.label("Data topic");
+                changed |= optional_field(ui, &mut self.config.mqtt_topic, false);
+                ui.end_row();
+
+                ui.label("Command topic");
+                optional_field(ui, &mut self.setup.command_topic, false);
+                ui.end_row();
+
+                ui.label("Alert topic");
+                optional_field(ui, &mut self.setup.alert_topic, false);
+                ui.end_row();
+            });
+        if changed {
+            self.setup.test = None;
+        }
+
+        ui.add_space(8.0);
+        self.render_connection_test(ui);
+    }
+
+    fn render_setup_theme(&mut self, ui: &mut egui::Ui) {
+        let mut selected = None;
+        egui::ScrollArea::vertical()
+            .max_height(300.0)
+            .show(ui, |ui| {
+                for theme in &self.themes {
+                    let is_selected = theme.name == self.current_theme;
+                    if ui.selectable_label(is_selected, &theme.name).clicked() {
+                        selected = Some(theme.name.clone());
+                    }
+                }
+            });
+
+        // Previewed right away; saved when setup finishes
+        if let Some(name) = selected {
+            self.current_theme = name;
+            self.apply_theme(ui.ctx());
+        }
+    }
+
+    fn render_setup_finish(&mut self, ui: &mut egui::Ui) {
+        let not_set = || "not set".to_string();
+        egui::Grid::new("setup_finish_grid")
+            .num_columns(2)
+            .spacing([16.0, 6.0])
+            .show(ui, |ui| {
+                ui.label("Broker");
+                ui.label(broker_uri(&self.config.mqtt_host));
+                ui.end_row();
+
+                ui.label("Username");
+                ui.label(
+                    self.config
+                        .mqtt_username
+                        .clone()
+                        .unwrap_or_else(|| "anonymous".to_string()),
+                );
+                ui.end_row();
+
+                ui.label("Data topic");
+                ui.label(self.config.mqtt_topic.clone().unwrap_or_else(not_set));
+                ui.end_row();
+
+                ui.label("Command topic");
+                ui.label(self.setup.command_topic.clone().unwrap_or_else(not_set));
+                ui.end_row();
+
+                ui.label("Alert topic");
+                ui.label(self.setup.alert_topic.clone().unwrap_or_else(not_set));
+                ui.end_row();
+
+                ui.label("Theme");
+                ui.label(&self.current_theme);
+                ui.end_row();
+            });
+
+        ui.add_space(8.0);
+        ui.label(
+            egui::RichText::new(format!(
+                "Settings are saved to {} and {}.",
+                self.config_path.display(),
+                self.env_path.display()
+            ))
+            .weak(),
+        );
+    }
+
+    /// Save what setup collected and connect to the data broker.
+    fn finish_setup(&mut self, ctx: &egui::Context) {
+        self.args.mqtt_command_topic = self.setup.command_topic.clone();
+        self.args.mqtt_alert_topic = self.setup.alert_topic.clone();
+        // The broker settings also go in .env, so one already set there
+        // doesn't override the wizard on the next reload or start
+        self.args.mqtt_host = Some(self.config.mqtt_host.clone());
+        self.args.mqtt_username = self.config.mqtt_username.clone();
+        self.args.mqtt_topic = self.config.mqtt_topic.clone();
+        // A password typed here is stored as a secret and replaces one in .env
+        let password_edited = self.edited_secrets.contains(&Secret::MqttPassword);
+        if password_edited {
+            self.args.mqtt_password = None;
+        }
+
+        let mut vars = vec![
+            ("MQTT_HOST", Some(self.config.mqtt_host.as_str())),
+            ("MQTT_USERNAME", self.config.mqtt_username.as_deref()),
+            ("MQTT_TOPIC", self.config.mqtt_topic.as_deref()),
+            ("MQTT_COMMAND_TOPIC", self.setup.command_topic.as_deref()),
+            ("MQTT_ALERT_TOPIC", self.setup.alert_topic.as_deref()),
+        ];
+        if password_edited {
+            vars.push(("MQTT_PASSWORD", None));
+        }
+        let written = write_env_file(&self.env_path, &vars);
+
+        self.save_config();
+        self.store_edited_secrets();
+
+        match written {
+            Ok(()) => info!(target: CONFIG, "Saved setup to {}", self.env_path.display()),
+            Err(e) => {
+                warn!(
+                    target: CONFIG,
+                    "Failed to write {}: {}",
+                    self.env_path.display(),
+                    e
+                );
+                self.save_error = Some(format!("{}: {}", self.env_path.display(), e));
+            }
+        }
+
+        self.setup = SetupWizard::new(&self.args);
+        self.restart_data_connection(ctx);
+    }
+
+    /// First-run wizard shown in place of the layout until a data topic is set up.
+    pub(crate) fn render_setup_wizard(&mut self, ui: &mut egui::Ui) {
+        let mut finish = false;
+
+        ui.vertical_centered(|ui| {
+            ui.add_space(ui.available_height() / 8.0);
+            ui.heading("Set up this kiosk");
+
+            if self.config.locked {
+                ui.add_space(12.0);
+                ui.label("Unlock the kiosk to set it up.");
+                return;
+            }
+
+            let step = self.setup.step;
+            ui.label(
+                egui::RichText::new(format!(
+                    "Step {} of {}: {}",
+                    step.index() + 1,
+                    SetupStep::ALL.len(),
+                    step.label()
+                ))
+                .weak(),
+            );
+            ui.add_space(12.0);
+
+            ui.allocate_ui(egui::vec2(480.0, 0.0), |ui| {
+                ui.vertical(|ui| match step {
+                    SetupStep::Broker => self.render_setup_broker(ui),
+                    SetupStep::Topics => self.render_setup_topics(ui),
+                    SetupStep::Theme => self.render_setup_theme(ui),
+                    SetupStep::Finish => self.render_setup_finish(ui),
+                });
+            });
+
+            ui.add_space(16.0);
+            ui.horizontal(|ui| {
+                let index = step.index();
+                if ui
+                    .add_enabled(index > 0, egui::Button::new("Back"))
+                    .clicked()
+                {
+                    self.setup.step = SetupStep::ALL[index - 1];
+                }
+
+                if step == SetupStep::Finish {
+                    finish = ui.button("Finish").clicked();
+                } else {
+                    let can_continue = match step {
+                        SetupStep::Broker => !self.config.mqtt_host.trim().is_empty(),
+                        SetupStep::Topics => self.config.mqtt_topic.is_some(),
+                        _ => true,
+                    };
+                    if ui
+                        .add_enabled(can_continue, egui::Button::new("Next"))
+                        .clicked()
+                    {
+                        self.setup.step = SetupStep::ALL[index + 1];
+                    }
+                }
+            });
+        });
+
+        if finish {
+            self.finish_setup(ui.ctx());
+        }
+    }
+}
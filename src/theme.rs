@@ -0,0 +1,576 @@
+use crate::logging::{MQTT_THEME, THEMES};
+use crate::theme_editor::toml_file_name;
+use eframe::egui;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, SystemTime};
+
+/// How often the themes directory is checked for changes.
+const THEME_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Which set of egui visuals a theme builds on.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ThemeBase {
+    Light,
+    Dark,
+}
+
+/// Colors are stored as `[r, g, b]` but may also be written as `"#rrggbb"`.
+pub(crate) mod rgb_format {
+    use serde::{Deserialize, Deserializer, de::Error};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Rgb {
+        Array([u8; 3]),
+        Hex(String),
+    }
+
+    fn parse_hex(hex: &str) -> Option<[u8; 3]> {
+        let hex = hex.trim().trim_start_matches('#');
+        if hex.len() != 6 || !hex.is_ascii() {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        Some([channel(0)?, channel(2)?, channel(4)?])
+    }
+
+    fn resolve<E: Error>(rgb: Rgb) -> Result<[u8; 3], E> {
+        match rgb {
+            Rgb::Array(color) => Ok(color),
+            Rgb::Hex(hex) => {
+                parse_hex(&hex).ok_or_else(|| E::custom(format!("invalid hex color '{}'", hex)))
+            }
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<[u8; 3], D::Error> {
+        resolve(Rgb::deserialize(d)?)
+    }
+
+    pub(crate) fn option<'de, D: Deserializer<'de>>(d: D) -> Result<Option<[u8; 3]>, D::Error> {
+        Option::<Rgb>::deserialize(d)?.map(resolve).transpose()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Theme {
+    pub(crate) name: String,
+    #[serde(deserialize_with = "rgb_format::deserialize")]
+    pub(crate) background_color: [u8; 3],
+    #[serde(deserialize_with = "rgb_format::deserialize")]
+    pub(crate) text_color: [u8; 3],
+    #[serde(deserialize_with = "rgb_format::deserialize")]
+    pub(crate) accent_color: [u8; 3],
+    /// Light or dark widget visuals; picked from the background brightness if unset
+    #[serde(default)]
+    pub(crate) base: Option<ThemeBase>,
+    #[serde(default, deserialize_with = "rgb_format::option")]
+    pub(crate) card_fill: Option<[u8; 3]>,
+    #[serde(default, deserialize_with = "rgb_format::option")]
+    pub(crate) card_stroke: Option<[u8; 3]>,
+    #[serde(default, deserialize_with = "rgb_format::option")]
+    pub(crate) weak_text_color: Option<[u8; 3]>,
+    /// Indicator color for live (realtime) departures
+    #[serde(default, deserialize_with = "rgb_format::option")]
+    pub(crate) live_color: Option<[u8; 3]>,
+    /// Indicator color for scheduled departures
+    #[serde(default, deserialize_with = "rgb_format::option")]
+    pub(crate) scheduled_color: Option<[u8; 3]>,
+    /// Corner radius of cards, in points
+    #[serde(default)]
+    pub(crate) corner_radius: Option<f32>,
+    /// Font family for all non-monospace text: "proportional", "monospace" or a loaded font
+    #[serde(default)]
+    pub(crate) font_family: Option<String>,
+    /// File a user theme was loaded from; `None` for built-in themes
+    #[serde(skip)]
+    pub(crate) path: Option<PathBuf>,
+    /// Whether the theme was pushed over MQTT rather than defined locally
+    #[serde(skip)]
+    pub(crate) remote: bool,
+}
+
+pub(crate) fn rgb(color: [u8; 3]) -> egui::Color32 {
+    egui::Color32::from_rgb(color[0], color[1], color[2])
+}
+
+/// Relative luminance as defined by WCAG 2, from 0.0 (black) to 1.0 (white).
+pub(crate) fn relative_luminance(color: [u8; 3]) -> f32 {
+    let channel = |c: u8| {
+        let c = c as f32 / 255.0;
+        if c <= 0.03928 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * channel(color[0]) + 0.7152 * channel(color[1]) + 0.0722 * channel(color[2])
+}
+
+/// WCAG contrast ratio between two colors, from 1.0 to 21.0.
+pub(crate) fn contrast_ratio(a: [u8; 3], b: [u8; 3]) -> f32 {
+    let (la, lb) = (relative_luminance(a), relative_luminance(b));
+    (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+}
+
+/// Below this text can hardly be seen at all. The editor warns well above it,
+/// but only themes this unreadable are rejected.
+const MIN_READABLE_CONTRAST: f32 = 1.5;
+const MAX_NAME_LENGTH: usize = 64;
+const MAX_CORNER_RADIUS: f32 = 64.0;
+
+impl Theme {
+    /// Check a theme from a file, another app or a layout before it is used.
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("theme name must not be empty".to_string());
+        }
+        if self.name.chars().any(char::is_control) {
+            return Err("theme name must not contain control characters".to_string());
+        }
+        if self.name.chars().count() > MAX_NAME_LENGTH {
+            return Err(format!(
+                "theme name must be at most {} characters",
+                MAX_NAME_LENGTH
+            ));
+        }
+
+        let mut fills = vec![("background", self.background_color)];
+        fills.extend(self.card_fill.map(|fill| ("card fill", fill)));
+        for (what, fill) in fills {
+            let ratio = contrast_ratio(self.text_color, fill);
+            if ratio < MIN_READABLE_CONTRAST {
+                return Err(format!(
+                    "text is unreadable on the {} (contrast {:.1}:1)",
+                    what, ratio
+                ));
+            }
+        }
+
+        if let Some(radius) = self.corner_radius
+            && !(0.0..=MAX_CORNER_RADIUS).contains(&radius)
+        {
+            return Err(format!(
+                "corner_radius must be between 0 and {}",
+                MAX_CORNER_RADIUS
+            ));
+        }
+        if self
+            .font_family
+            .as_deref()
+            .is_some_and(|family| family.trim().is_empty())
+        {
+            return Err("font_family must not be empty".to_string());
+        }
+        Ok(())
+    }
+
+    pub(crate) fn base(&self) -> ThemeBase {
+        self.base.unwrap_or_else(|| {
+            if relative_luminance(self.background_color) > 0.5 {
+                ThemeBase::Light
+            } else {
+                ThemeBase::Dark
+            }
+        })
+    }
+
+    pub(crate) fn accent(&self) -> egui::Color32 {
+        rgb(self.accent_color)
+    }
+
+    pub(crate) fn live(&self) -> egui::Color32 {
+        self.live_color
+            .map(rgb)
+            .unwrap_or(egui::Color32::from_rgb(76, 175, 80))
+    }
+
+    pub(crate) fn corner_radius(&self) -> f32 {
+        self.corner_radius.unwrap_or(12.0).max(0.0)
+    }
+
+    /// Radius for frames nested inside a card.
+    pub(crate) fn inner_corner_radius(&self) -> f32 {
+        self.corner_radius() * 2.0 / 3.0
+    }
+
+    /// Build the egui visuals for this theme.
+    pub(crate) fn visuals(&self) -> egui::Visuals {
+        let mut visuals = match self.base() {
+            ThemeBase::Light => egui::Visuals::light(),
+            ThemeBase::Dark => egui::Visuals::dark(),
+        };
+
+        visuals.panel_fill = rgb(self.background_color);
+
+        visuals.extreme_bg_color = egui::Color32::from_rgb(
+            self.background_color[0].saturating_add(10),
+            self.background_color[1].saturating_add(10),
+            self.background_color[2].saturating_add(10),
+        );
+
+        visuals.override_text_color = Some(rgb(self.text_color));
+        visuals.weak_text_color = self.weak_text_color.map(rgb);
+
+        if let Some(card_fill) = self.card_fill {
+            visuals.faint_bg_color = rgb(card_fill);
+        }
+        if let Some(card_stroke) = self.card_stroke {
+            visuals.widgets.noninteractive.bg_stroke.color = rgb(card_stroke);
+        }
+        visuals.hyperlink_color = self.accent();
+        visuals.selection.bg_fill = self.accent().gamma_multiply(0.6);
+
+        visuals
+    }
+
+    /// The font family to use for body text, if the theme sets one that exists.
+    pub(crate) fn font_family(&self, ctx: &egui::Context) -> egui::FontFamily {
+        let Some(name) = self.font_family.as_deref() else {
+            return egui::FontFamily::Proportional;
+        };
+
+        let family = match name.to_lowercase().as_str() {
+            "proportional" => egui::FontFamily::Proportional,
+            "monospace" => egui::FontFamily::Monospace,
+            _ => egui::FontFamily::Name(name.into()),
+        };
+
+        if ctx.fonts(|f| f.families()).contains(&family) {
+            family
+        } else {
+            warn!(target: THEMES, "Theme '{}': Unknown font family '{}'", self.name, name);
+            egui::FontFamily::Proportional
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            name: "Dark".to_string(),
+            background_color: [40, 44, 52],
+            text_color: [220, 223, 228],
+            accent_color: [255, 180, 100],
+            base: None,
+            card_fill: None,
+            card_stroke: None,
+            weak_text_color: None,
+            live_color: None,
+            scheduled_color: None,
+            corner_radius: None,
+            font_family: None,
+            path: None,
+            remote: false,
+        }
+    }
+}
+
+pub(crate) fn create_default_themes() -> Vec<Theme> {
+    vec![
+        Theme {
+            name: "Light".to_string(),
+            background_color: [240, 240, 245],
+            text_color: [60, 60, 70],
+            accent_color: [100, 100, 180],
+            ..Default::default()
+        },
+        Theme {
+            name: "Dark".to_string(),
+            background_color: [40, 44, 52],
+            text_color: [220, 223, 228],
+            accent_color: [255, 180, 100],
+            ..Default::default()
+        },
+        Theme {
+            name: "Solarized".to_string(),
+            background_color: [0, 43, 54],
+            text_color: [131, 148, 150],
+            accent_color: [181, 137, 0],
+            ..Default::default()
+        },
+        Theme {
+            name: "After Dark".to_string(),
+            background_color: [32, 29, 101],
+            text_color: [172, 171, 213],
+            accent_color: [254, 243, 199],
+            ..Default::default()
+        },
+        Theme {
+            name: "Her".to_string(),
+            background_color: [101, 29, 29],
+            text_color: [213, 171, 171],
+            accent_color: [254, 243, 199],
+            ..Default::default()
+        },
+        Theme {
+            name: "Forest".to_string(),
+            background_color: [5, 46, 22],
+            text_color: [134, 239, 172],
+            accent_color: [254, 243, 199],
+            ..Default::default()
+        },
+        Theme {
+            name: "Sky".to_string(),
+            background_color: [8, 47, 73],
+            text_color: [125, 211, 252],
+            accent_color: [254, 243, 199],
+            ..Default::default()
+        },
+        Theme {
+            name: "Clays".to_string(),
+            background_color: [69, 26, 3],
+            text_color: [245, 158, 11],
+            accent_color: [254, 243, 199],
+            ..Default::default()
+        },
+        Theme {
+            name: "Stones".to_string(),
+            background_color: [41, 37, 36],
+            text_color: [156, 163, 175],
+            accent_color: [254, 243, 199],
+            ..Default::default()
+        },
+    ]
+}
+
+/// Mapping from theme names used by the other neiam apps to kiosk theme names.
+///
+/// Entries in `Config::theme_aliases` take precedence over these.
+pub(crate) fn default_theme_aliases() -> HashMap<String, String> {
+    [
+        ("light", "Light"),
+        ("light-soft", "Light"),
+        ("dark", "Dark"),
+        ("dark-soft", "Dark"),
+        ("dark-dimmed", "Dark"),
+        ("after-dark", "After Dark"),
+        ("her", "Her"),
+        ("forest", "Forest"),
+        ("sky", "Sky"),
+        ("clays", "Clays"),
+        ("stones", "Stones"),
+        ("solarized", "Solarized"),
+    ]
+    .into_iter()
+    .map(|(alias, name)| (alias.to_string(), name.to_string()))
+    .collect()
+}
+
+/// Read `Config::theme_aliases` with lowercase keys, since incoming names are
+/// lowercased before they are looked up.
+pub(crate) fn lowercase_aliases<'de, D: serde::Deserializer<'de>>(
+    d: D,
+) -> Result<HashMap<String, String>, D::Error> {
+    let aliases = HashMap::<String, String>::deserialize(d)?;
+    Ok(aliases
+        .into_iter()
+        .map(|(alias, name)| (alias.trim().to_lowercase(), name))
+        .collect())
+}
+
+/// A theme change received from the theme sync topic.
+#[derive(Debug)]
+pub(crate) struct ThemeUpdate {
+    pub(crate) name: String,
+    /// Full definition for themes the kiosk may not know about
+    pub(crate) definition: Option<Theme>,
+    /// Identifies the publishing kiosk, so we can ignore our own messages
+    pub(crate) source: Option<String>,
+}
+
+/// Parse a theme sync payload.
+///
+/// Accepts `{"theme": "name"}`, optionally with a `"colors"` object holding the
+/// remaining `Theme` fields so the sender can push a theme the kiosk has never seen.
+pub(crate) fn parse_theme_message(payload: &str) -> Option<ThemeUpdate> {
+    let json: serde_json::Value = serde_json::from_str(payload).ok()?;
+    let name = json.get("theme")?.as_str()?.trim().to_string();
+    if name.is_empty() {
+        return None;
+    }
+
+    let definition = match json.get("colors") {
+        Some(serde_json::Value::Object(colors)) => {
+            let mut fields = colors.clone();
+            fields.insert("name".to_string(), serde_json::Value::String(name.clone()));
+            match serde_json::from_value::<Theme>(serde_json::Value::Object(fields))
+                .map_err(|e| e.to_string())
+                .and_then(|theme| theme.validate().map(|()| theme))
+            {
+                Ok(mut theme) => {
+                    theme.remote = true;
+                    Some(theme)
+                }
+                Err(e) => {
+                    warn!(target: MQTT_THEME, "Invalid colors for '{}': {}", name, e);
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+
+    let source = json
+        .get("source")
+        .and_then(|s| s.as_str())
+        .map(str::to_string);
+
+    Some(ThemeUpdate {
+        name,
+        definition,
+        source,
+    })
+}
+
+/// Themes loaded from the user's themes directory, plus any files that failed to load.
+#[derive(Debug, Default)]
+pub(crate) struct UserThemes {
+    pub(crate) themes: Vec<Theme>,
+    pub(crate) errors: Vec<String>,
+}
+
+fn is_theme_file(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("toml") | Some("json")
+    )
+}
+
+fn parse_theme_file(path: &Path) -> Result<Theme, String> {
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let mut theme: Theme = match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => serde_json::from_str(&contents).map_err(|e| e.to_string())?,
+        _ => toml::from_str(&contents).map_err(|e| e.to_string())?,
+    };
+
+    theme.name = theme.name.trim().to_string();
+    theme.validate()?;
+
+    theme.path = Some(path.to_path_buf());
+    Ok(theme)
+}
+
+/// Load every `*.toml` and `*.json` theme in `dir`, in file name order.
+///
+/// Themes whose names clash with a built-in or an earlier file are rejected.
+pub(crate) fn load_user_themes(dir: &Path) -> UserThemes {
+    let mut result = UserThemes::default();
+
+    let Ok(entries) = fs::read_dir(dir) else {
+        return result;
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.is_file() && is_theme_file(p))
+        .collect();
+    paths.sort();
+
+    let builtin_names: Vec<String> = create_default_themes()
+        .into_iter()
+        .map(|t| t.name)
+        .collect();
+
+    for path in paths {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        match parse_theme_file(&path) {
+            Ok(theme) if builtin_names.contains(&theme.name) => result.errors.push(format!(
+                "{}: '{}' is a built-in theme name",
+                file_name, theme.name
+            )),
+            Ok(theme) if result.themes.iter().any(|t| t.name == theme.name) => result.errors.push(
+                format!("{}: duplicate theme name '{}'", file_name, theme.name),
+            ),
+            Ok(theme) => result.themes.push(theme),
+            Err(e) => result.errors.push(format!("{}: {}", file_name, e)),
+        }
+    }
+
+    for error in &result.errors {
+        warn!(target: THEMES, "{}", error);
+    }
+
+    result
+}
+
+/// Where definitions received over MQTT are kept, so they survive a restart.
+///
+/// A subdirectory of the themes directory, which the watcher doesn't look into.
+pub(crate) fn received_themes_dir(themes_dir: &Path) -> PathBuf {
+    themes_dir.join("received")
+}
+
+/// Themes received over MQTT on earlier runs.
+pub(crate) fn load_received_themes(themes_dir: &Path) -> Vec<Theme> {
+    load_user_themes(&received_themes_dir(themes_dir))
+        .themes
+        .into_iter()
+        .map(|mut theme| {
+            theme.path = None;
+            theme.remote = true;
+            theme
+        })
+        .collect()
+}
+
+/// Keep a definition received over MQTT for the next start.
+pub(crate) fn save_received_theme(themes_dir: &Path, theme: &Theme) {
+    let dir = received_themes_dir(themes_dir);
+    let path = dir.join(toml_file_name(&theme.name));
+    let result = fs::create_dir_all(&dir)
+        .map_err(|e| e.to_string())
+        .and_then(|()| toml::to_string_pretty(theme).map_err(|e| e.to_string()))
+        .and_then(|s| fs::write(&path, s).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        warn!(target: MQTT_THEME, "Failed to save {}: {}", path.display(), e);
+    }
+}
+
+/// Modification times of the theme files in `dir`, used to detect changes.
+fn scan_theme_dir(dir: &Path) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+
+    let mut files: Vec<_> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| is_theme_file(&e.path()))
+        .map(|e| (e.path(), e.metadata().and_then(|m| m.modified()).ok()))
+        .collect();
+    files.sort();
+    files
+}
+
+/// Watch the themes directory in a background thread, sending freshly loaded
+/// themes whenever a file is added, changed or removed.
+pub(crate) fn spawn_theme_watcher(dir: PathBuf, ctx: egui::Context) -> Receiver<UserThemes> {
+    let (tx, rx) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        let mut last_scan = scan_theme_dir(&dir);
+        loop {
+            std::thread::sleep(THEME_POLL_INTERVAL);
+
+            let scan = scan_theme_dir(&dir);
+            if scan == last_scan {
+                continue;
+            }
+            last_scan = scan;
+
+            info!(target: THEMES, "Change detected in {}, reloading", dir.display());
+            if tx.send(load_user_themes(&dir)).is_err() {
+                break;
+            }
+            ctx.request_repaint();
+        }
+    });
+
+    rx
+}
@@ -0,0 +1,405 @@
+use crate::ApollosKiosk;
+use crate::logging::THEMES;
+use crate::profile::name_hash;
+use crate::theme::{ThemeBase, contrast_ratio, create_default_themes};
+use eframe::egui;
+use log::{info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// WCAG AA minimum contrast for body text.
+const MIN_TEXT_CONTRAST: f32 = 4.5;
+/// WCAG AA minimum contrast for large text and graphics such as the accent color.
+const MIN_LARGE_CONTRAST: f32 = 3.0;
+
+/// Flatten a (possibly translucent) egui color onto an opaque background.
+fn blend_over(color: egui::Color32, background: [u8; 3]) -> [u8; 3] {
+    let [r, g, b, a] = color.to_array();
+    let remaining = 1.0 - a as f32 / 255.0;
+    let mix = |c: u8, bg: u8| (c as f32 + bg as f32 * remaining).round().min(255.0) as u8;
+    [
+        mix(r, background[0]),
+        mix(g, background[1]),
+        mix(b, background[2]),
+    ]
+}
+
+/// Turn a theme or layout name into a file name, e.g. "After Dark" -> "after-dark.toml".
+pub(crate) fn toml_file_name(name: &str) -> String {
+    let slug: String = name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let slug = slug.trim_matches('-');
+    if slug.is_empty() {
+        // Names without ASCII letters or digits, e.g. "夜", still need their
+        // own visible file
+        format!("untitled-{:08x}.toml", name_hash(name.trim()))
+    } else {
+        format!("{}.toml", slug)
+    }
+}
+
+/// Path for a new file named after `name` in `dir`, numbered when another
+/// name already uses that file, e.g. "after-dark-2.toml".
+pub(crate) fn new_toml_path(dir: &Path, name: &str) -> PathBuf {
+    let file_name = toml_file_name(name);
+    let stem = file_name.trim_end_matches(".toml");
+    (1..)
+        .map(|n| match n {
+            1 => dir.join(&file_name),
+            n => dir.join(format!("{}-{}.toml", stem, n)),
+        })
+        .find(|path| !path.exists())
+        .expect("some numbered file name is free")
+}
+
+/// Color picker row for a required theme color.
+fn color_row(ui: &mut egui::Ui, label: &str, color: &mut [u8; 3]) -> bool {
+    ui.label(label);
+    let changed = egui::color_picker::color_edit_button_srgb(ui, color).changed();
+    ui.end_row();
+    changed
+}
+
+/// Color picker row for an optional theme color, with a checkbox to override the default.
+fn optional_color_row(
+    ui: &mut egui::Ui,
+    label: &str,
+    color: &mut Option<[u8; 3]>,
+    default: [u8; 3],
+) -> bool {
+    ui.label(label);
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        let mut enabled = color.is_some();
+        if ui.checkbox(&mut enabled, "").changed() {
+            *color = enabled.then_some(default);
+            changed = true;
+        }
+        if let Some(c) = color {
+            changed |= egui::color_picker::color_edit_button_srgb(ui, c).changed();
+        } else {
+            ui.label(egui::RichText::new("default").weak());
+        }
+    });
+    ui.end_row();
+    changed
+}
+
+fn contrast_warning(ui: &mut egui::Ui, what: &str, ratio: f32, minimum: f32) {
+    if ratio < minimum {
+        ui.label(
+            egui::RichText::new(format!(
+                "⚠ {} contrast is {:.1}:1 (at least {:.1}:1 recommended)",
+                what, ratio, minimum
+            ))
+            .color(egui::Color32::from_rgb(255, 180, 100)),
+        );
+    }
+}
+
+impl ApollosKiosk {
+    /// Start editing a copy of the current theme; the layout previews it live.
+    pub(crate) fn open_theme_editor(&mut self, ctx: &egui::Context) {
+        let mut draft = self.get_current_theme();
+        if draft.path.is_none() {
+            draft.name = format!("{} Custom", draft.name);
+        }
+        self.theme_editor = Some(draft);
+        self.theme_editor_status = None;
+        self.apply_theme(ctx);
+    }
+
+    pub(crate) fn close_theme_editor(&mut self, ctx: &egui::Context) {
+        self.theme_editor = None;
+        self.theme_editor_status = None;
+        self.apply_theme(ctx);
+    }
+
+    /// Write the draft theme to the themes directory and select it.
+    ///
+    /// With `as_new` set, refuses to replace an existing theme of the same name.
+    fn save_theme_draft(&mut self, ctx: &egui::Context, as_new: bool) {
+        let Some(mut draft) = self.theme_editor.clone() else {
+            return;
+        };
+        draft.name = draft.name.trim().to_string();
+
+        if let Err(e) = draft.validate() {
+            self.theme_editor_status = Some(format!("Can't save: {}", e));
+            return;
+        }
+        if create_default_themes().iter().any(|t| t.name == draft.name) {
+            self.theme_editor_status = Some(format!("'{}' is a built-in theme", draft.name));
+            return;
+        }
+
+        let existing = self.themes.iter().find(|t| t.name == draft.name);
+        let path = match (existing.and_then(|t| t.path.clone()), as_new) {
+            // Renaming onto another theme's name would replace that theme
+            (Some(path), as_new) if as_new || draft.path.as_ref() != Some(&path) => {
+                self.theme_editor_status =
+                    Some(format!("A theme named '{}' already exists", draft.name));
+                return;
+            }
+            (Some(path), _) => path,
+            (None, as_new) => match &draft.path {
+                // A renamed theme keeps its file when the new name maps to the same one
+                Some(old)
+                    if !as_new && old.file_name() == Some(toml_file_name(&draft.name).as_ref()) =>
+                {
+                    old.clone()
+                }
+                _ => new_toml_path(&self.themes_dir, &draft.name),
+            },
+        };
+        // Saving a renamed theme replaces its old file rather than leaving a copy
+        let renamed_from = draft.path.clone().filter(|old| !as_new && *old != path);
+
+        let result = toml::to_string_pretty(&draft)
+            .map_err(|e| e.to_string())
+            .and_then(|s| fs::write(&path, s).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            warn!(target: THEMES, "Failed to save {}: {}", path.display(), e);
+            self.theme_editor_status = Some(format!("Failed to save: {}", e));
+            return;
+        }
+        info!(target: THEMES, "Saved '{}' to {}", draft.name, path.display());
+        if let Some(old) = &renamed_from {
+            match fs::remove_file(old) {
+                Ok(()) => info!(target: THEMES, "Removed {} after renaming", old.display()),
+                Err(e) => warn!(
+                    target: THEMES,
+                    "Failed to remove {} after renaming: {}",
+                    old.display(),
+                    e
+                ),
+            }
+            self.themes.retain(|t| t.path.as_ref() != Some(old));
+        }
+
+        // Use it right away rather than waiting for the watcher to notice the file
+        draft.path = Some(path);
+        let name = draft.name.clone();
+        self.themes.retain(|t| t.name != name);
+        self.themes.push(draft);
+
+        self.close_theme_editor(ctx);
+        self.select_theme(ctx, name);
+    }
+
+    pub(crate) fn render_theme_editor(&mut self, ctx: &egui::Context) {
+        let Some(mut draft) = self.theme_editor.clone() else {
+            return;
+        };
+
+        let mut open = true;
+        let mut changed = false;
+        let mut save = None;
+        let mut cancel = false;
+        let families = ctx.fonts(|f| f.families());
+
+        egui::Window::new("Theme Editor")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Name:");
+                    ui.text_edit_singleline(&mut draft.name);
+                });
+                ui.add_space(8.0);
+
+                egui::Grid::new("theme_editor_grid")
+                    .num_columns(2)
+                    .spacing([16.0, 6.0])
+                    .show(ui, |ui| {
+                        let visuals = draft.visuals();
+                        let bg = draft.background_color;
+                        let weak_default = blend_over(visuals.weak_text_color(), bg);
+                        let fill_default = blend_over(visuals.faint_bg_color, bg);
+                        let stroke_default =
+                            blend_over(visuals.widgets.noninteractive.bg_stroke.color, bg);
+                        let live_default = blend_over(draft.live(), bg);
+
+                        changed |= color_row(ui, "Background", &mut draft.background_color);
+                        changed |= color_row(ui, "Text", &mut draft.text_color);
+                        changed |= color_row(ui, "Accent", &mut draft.accent_color);
+                        changed |= optional_color_row(
+                            ui,
+                            "Weak text",
+                            &mut draft.weak_text_color,
+                            weak_default,
+                        );
+                        changed |=
+                            optional_color_row(ui, "Card fill", &mut draft.card_fill, fill_default);
+                        changed |= optional_color_row(
+                            ui,
+                            "Card stroke",
+                            &mut draft.card_stroke,
+                            stroke_default,
+                        );
+                        changed |= optional_color_row(
+                            ui,
+                            "Live indicator",
+                            &mut draft.live_color,
+                            live_default,
+                        );
+                        changed |= optional_color_row(
+                            ui,
+                            "Scheduled indicator",
+                            &mut draft.scheduled_color,
+                            weak_default,
+                        );
+
+                        ui.label("Base");
+                        ui.horizontal(|ui| {
+                            for (base, label) in [
+                                (None, "Auto"),
+                                (Some(ThemeBase::Light), "Light"),
+                                (Some(ThemeBase::Dark), "Dark"),
+                            ] {
+                                if ui.selectable_label(draft.base == base, label).clicked() {
+                                    draft.base = base;
+                                    changed = true;
+                                }
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label("Corner radius");
+                        let mut radius = draft.corner_radius();
+                        if ui
+                            .add(egui::Slider::new(&mut radius, 0.0..=32.0).step_by(1.0))
+                            .changed()
+                        {
+                            draft.corner_radius = Some(radius);
+                            changed = true;
+                        }
+                        ui.end_row();
+
+                        ui.label("Font family");
+                        let selected = draft.font_family.clone().unwrap_or_default();
+                        egui::ComboBox::from_id_salt("theme_editor_font")
+                            .selected_text(if selected.is_empty() {
+                                "Default"
+                            } else {
+                                &selected
+                            })
+                            .show_ui(ui, |ui| {
+                                if ui
+                                    .selectable_label(selected.is_empty(), "Default")
+                                    .clicked()
+                                {
+                                    draft.font_family = None;
+                                    changed = true;
+                                }
+                                for family in &families {
+                                    let name = family.to_string();
+                                    if ui.selectable_label(selected == name, &name).clicked() {
+                                        draft.font_family = Some(name);
+                                        changed = true;
+                                    }
+                                }
+                            });
+                        ui.end_row();
+                    });
+
+                ui.add_space(8.0);
+                let visuals = draft.visuals();
+                let weak = blend_over(visuals.weak_text_color(), draft.background_color);
+                contrast_warning(
+                    ui,
+                    "Text",
+                    contrast_ratio(draft.text_color, draft.background_color),
+                    MIN_TEXT_CONTRAST,
+                );
+                if let Some(card_fill) = draft.card_fill {
+                    contrast_warning(
+                        ui,
+                        "Card text",
+                        contrast_ratio(draft.text_color, card_fill),
+                        MIN_TEXT_CONTRAST,
+                    );
+                }
+                contrast_warning(
+                    ui,
+                    "Weak text",
+                    contrast_ratio(weak, draft.background_color),
+                    MIN_LARGE_CONTRAST,
+                );
+                contrast_warning(
+                    ui,
+                    "Accent",
+                    contrast_ratio(draft.accent_color, draft.background_color),
+                    MIN_LARGE_CONTRAST,
+                );
+
+                if let Some(status) = &self.theme_editor_status {
+                    ui.label(
+                        egui::RichText::new(status).color(egui::Color32::from_rgb(255, 100, 100)),
+                    );
+                }
+
+                ui.add_space(8.0);
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if draft.path.is_some() && ui.button("💾 Save").clicked() {
+                        save = Some(false);
+                    }
+                    if ui.button("💾 Save as New Theme").clicked() {
+                        save = Some(true);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        cancel = true;
+                    }
+                });
+            });
+
+        self.theme_editor = Some(draft);
+        if changed {
+            self.theme_editor_status = None;
+            self.apply_theme(ctx);
+        }
+
+        if let Some(as_new) = save {
+            self.save_theme_draft(ctx, as_new);
+        } else if cancel || !open {
+            self.close_theme_editor(ctx);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_names_are_lowercase_slugs() {
+        assert_eq!(toml_file_name("After Dark"), "after-dark.toml");
+        assert_eq!(
+            toml_file_name("  Night / Day (v2) "),
+            "night---day--v2.toml"
+        );
+        assert_eq!(toml_file_name("Café"), "caf.toml");
+    }
+
+    #[test]
+    fn names_without_ascii_get_a_stable_untitled_name() {
+        let name = toml_file_name("夜");
+        assert!(
+            name.starts_with("untitled-") && name.ends_with(".toml"),
+            "{}",
+            name
+        );
+        assert_eq!(name, toml_file_name(" 夜 "));
+        assert_ne!(name, toml_file_name("昼"));
+    }
+}
@@ -0,0 +1,434 @@
+use crate::logging::MQTT_THEME;
+use crate::theme::{self, Theme, ThemeUpdate};
+use crate::{ApollosKiosk, Config, profile};
+use eframe::egui;
+use log::{debug, error, info, warn};
+use paho_mqtt as mqtt;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Wait before retrying a failed first connect, doubling up to the maximum.
+const CONNECT_RETRY: Duration = Duration::from_secs(2);
+const CONNECT_RETRY_MAX: Duration = Duration::from_secs(60);
+
+/// Run `attempt` until it succeeds, since auto-reconnect only covers dropped
+/// connections and not the first connect or subscribe.
+///
+/// `failed` is told each error and the delay before the next try, and `wait`
+/// waits out that delay, returning false to give up because the connection is
+/// being stopped. Returns whether `attempt` succeeded.
+pub(crate) fn retry_with_backoff(
+    mut attempt: impl FnMut() -> Result<(), String>,
+    mut failed: impl FnMut(&str, Duration),
+    mut wait: impl FnMut(Duration) -> bool,
+) -> bool {
+    let mut retry = CONNECT_RETRY;
+    loop {
+        let Err(e) = attempt() else {
+            return true;
+        };
+        failed(&e, retry);
+        if !wait(retry) {
+            return false;
+        }
+        retry = (retry * 2).min(CONNECT_RETRY_MAX);
+    }
+}
+
+/// Identifies this kiosk process in published theme messages.
+pub(crate) fn sync_source_id() -> String {
+    let host = std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+        .unwrap_or_else(|| "kiosk".to_string());
+    format!("apollos-kiosk-{}-{}", host, std::process::id())
+}
+
+/// Connection state shown in the theme sync settings.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum SyncStatus {
+    Connecting,
+    Connected,
+    Reconnecting,
+    Failed(String),
+}
+
+impl SyncStatus {
+    pub(crate) fn show(&self, ui: &mut egui::Ui) {
+        let (text, color) = match self {
+            SyncStatus::Connecting => ("Connecting...".to_string(), egui::Color32::GRAY),
+            SyncStatus::Connected => (
+                "Connected".to_string(),
+                egui::Color32::from_rgb(76, 175, 80),
+            ),
+            SyncStatus::Reconnecting => (
+                "Disconnected, reconnecting...".to_string(),
+                egui::Color32::from_rgb(255, 180, 100),
+            ),
+            SyncStatus::Failed(e) => (
+                format!("Failed: {}", e),
+                egui::Color32::from_rgb(255, 100, 100),
+            ),
+        };
+        ui.horizontal(|ui| {
+            ui.label(egui::RichText::new("●").color(color));
+            ui.label(text);
+        });
+    }
+}
+
+/// Work for the sync thread: messages from the broker and themes to publish.
+enum SyncEvent {
+    Received(Option<mqtt::Message>),
+    Publish(mqtt::Message),
+    Stop,
+}
+
+/// Connection to the theme sync broker, shared by the sync thread and the UI.
+///
+/// Dropping it tells the sync thread to disconnect and stop; the UI thread
+/// never waits on the broker.
+pub(crate) struct ThemeSync {
+    client: mqtt::Client,
+    topic: String,
+    source_id: String,
+    events: Sender<SyncEvent>,
+    worker: Option<JoinHandle<()>>,
+    error: Arc<Mutex<Option<String>>>,
+    connected_once: Arc<Mutex<bool>>,
+}
+
+impl ThemeSync {
+    /// Connect and subscribe in a background thread, forwarding theme updates to `tx`.
+    ///
+    /// `previous` is the thread of the sync being replaced. It uses the same
+    /// client id, so the new one only connects once it has finished.
+    pub(crate) fn start(
+        config: &Config,
+        source_id: String,
+        instance: Option<&str>,
+        theme_tx: Sender<ThemeUpdate>,
+        theme_ctx: egui::Context,
+        previous: Option<JoinHandle<()>>,
+    ) -> Option<Self> {
+        let theme_host = config.mqtt_theme_host.clone();
+        let theme_username = config
+            .mqtt_theme_username
+            .clone()
+            .unwrap_or_else(|| "kiosk-theme".to_string());
+        let theme_password = config.mqtt_theme_password.clone().unwrap_or_default();
+        let theme_topic = config.mqtt_theme_topic.clone();
+
+        let create_opts = mqtt::CreateOptionsBuilder::new()
+            .server_uri(theme_host)
+            .client_id(profile::mqtt_client_id(
+                &format!("{}-client", theme_username),
+                instance,
+            ))
+            .finalize();
+
+        let cli = match mqtt::Client::new(create_opts) {
+            Ok(cli) => cli,
+            Err(e) => {
+                error!(target: MQTT_THEME, "Failed to create client: {:?}", e);
+                return None;
+            }
+        };
+
+        let error = Arc::new(Mutex::new(None));
+        let connected_once = Arc::new(Mutex::new(false));
+        let (events, events_rx) = mpsc::channel();
+
+        // Feed broker messages into the same queue as publish requests
+        let rx_mqtt = cli.start_consuming();
+        let forward = events.clone();
+        std::thread::spawn(move || {
+            for msg in rx_mqtt.iter() {
+                if forward.send(SyncEvent::Received(msg)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let thread_cli = cli.clone();
+        let thread_topic = theme_topic.clone();
+        let thread_source_id = source_id.clone();
+        let thread_error = error.clone();
+        let thread_connected_once = connected_once.clone();
+
+        let worker = std::thread::spawn(move || {
+            let cli = thread_cli;
+            let theme_topic = thread_topic;
+            let fail = |message: String| {
+                *thread_error.lock().unwrap() = Some(message);
+                theme_ctx.request_repaint();
+            };
+            if let Some(previous) = previous {
+                let _ = previous.join();
+            }
+
+            // The last selection made before connecting is published once connected
+            let mut pending = None;
+            let conn_opts = mqtt::ConnectOptionsBuilder::new()
+                .keep_alive_interval(Duration::from_secs(20))
+                .connect_timeout(Duration::from_secs(10))
+                .clean_session(true)
+                .automatic_reconnect(Duration::from_secs(1), Duration::from_secs(30))
+                .user_name(&theme_username)
+                .password(&theme_password)
+                .finalize();
+            let connected = retry_with_backoff(
+                || {
+                    if !cli.is_connected() {
+                        cli.connect(conn_opts.clone()).map_err(|e| e.to_string())?;
+                    }
+                    cli.subscribe(&theme_topic, 1)
+                        .map(|_| ())
+                        .map_err(|e| format!("subscribe to '{}': {}", theme_topic, e))
+                },
+                |e, retry| {
+                    error!(
+                        target: MQTT_THEME,
+                        "Failed to connect, retrying in {}s: {}",
+                        retry.as_secs(),
+                        e
+                    );
+                    fail(format!("{} (retrying)", e));
+                },
+                |retry| {
+                    let deadline = Instant::now() + retry;
+                    while let Some(wait) = deadline.checked_duration_since(Instant::now()) {
+                        match events_rx.recv_timeout(wait) {
+                            Ok(SyncEvent::Publish(msg)) => pending = Some(msg),
+                            Ok(SyncEvent::Received(_)) => {}
+                            Ok(SyncEvent::Stop) | Err(mpsc::RecvTimeoutError::Disconnected) => {
+                                return false;
+                            }
+                            Err(mpsc::RecvTimeoutError::Timeout) => break,
+                        }
+                    }
+                    true
+                },
+            );
+            if !connected {
+                disconnect(&cli);
+                info!(target: MQTT_THEME, "Stopped");
+                return;
+            }
+            *thread_error.lock().unwrap() = None;
+
+            info!(target: MQTT_THEME, "Connected and subscribed to {}", theme_topic);
+            *thread_connected_once.lock().unwrap() = true;
+            theme_ctx.request_repaint();
+            if let Some(msg) = pending {
+                publish(&cli, msg);
+            }
+
+            for event in events_rx.iter() {
+                let msg = match event {
+                    SyncEvent::Publish(msg) => {
+                        publish(&cli, msg);
+                        continue;
+                    }
+                    SyncEvent::Received(msg) => msg,
+                    SyncEvent::Stop => break,
+                };
+                if let Some(msg) = msg {
+                    debug!(target: MQTT_THEME, "Received message on '{}'", msg.topic());
+
+                    if msg.topic() == theme_topic
+                        && let Some(update) = theme::parse_theme_message(&msg.payload_str())
+                    {
+                        // Only our own publishes carry our source id
+                        if update.source.as_deref() == Some(thread_source_id.as_str()) {
+                            debug!(target: MQTT_THEME, "Ignoring echo of our own message");
+                            continue;
+                        }
+
+                        debug!(target: MQTT_THEME, "Parsed theme name: {}", update.name);
+                        let _ = theme_tx.send(update);
+                        theme_ctx.request_repaint();
+                    }
+                } else {
+                    // None indicates a disconnection, but with auto-reconnect enabled
+                    // the client will handle reconnection automatically
+                    warn!(target: MQTT_THEME, "Disconnected, waiting for reconnection...");
+                    theme_ctx.request_repaint();
+                }
+            }
+            disconnect(&cli);
+            info!(target: MQTT_THEME, "Stopped");
+        });
+
+        Some(ThemeSync {
+            client: cli,
+            topic: theme_topic,
+            source_id,
+            events,
+            worker: Some(worker),
+            error,
+            connected_once,
+        })
+    }
+
+    pub(crate) fn status(&self) -> SyncStatus {
+        if let Some(e) = self.error.lock().unwrap().clone() {
+            SyncStatus::Failed(e)
+        } else if self.client.is_connected() {
+            SyncStatus::Connected
+        } else if *self.connected_once.lock().unwrap() {
+            SyncStatus::Reconnecting
+        } else {
+            SyncStatus::Connecting
+        }
+    }
+
+    /// Publish a theme selection as `{"theme": ..., "source": ...}`.
+    ///
+    /// Themes other apps can't know about (user or pushed themes) also carry
+    /// their `colors` so receivers can apply them. The sync thread sends it,
+    /// so a slow broker never holds up the UI.
+    pub(crate) fn publish(&self, sync_name: &str, definition: Option<&Theme>) {
+        let mut payload = serde_json::json!({
+            "theme": sync_name,
+            "source": self.source_id,
+        });
+
+        if let Some(definition) = definition
+            && let Ok(serde_json::Value::Object(mut colors)) = serde_json::to_value(definition)
+        {
+            colors.remove("name");
+            payload["colors"] = serde_json::Value::Object(colors);
+        }
+
+        let msg = mqtt::Message::new_retained(&self.topic, payload.to_string(), 1);
+        if self.events.send(SyncEvent::Publish(msg)).is_err() {
+            warn!(target: MQTT_THEME, "Sync thread has stopped, not publishing {}", sync_name);
+        }
+    }
+
+    /// Ask the sync thread to disconnect, returning it so a replacement can
+    /// wait for it.
+    fn shut_down(&mut self) -> Option<JoinHandle<()>> {
+        let _ = self.events.send(SyncEvent::Stop);
+        self.worker.take()
+    }
+}
+
+fn disconnect(cli: &mqtt::Client) {
+    if cli.is_connected()
+        && let Err(e) = cli.disconnect(None)
+    {
+        warn!(target: MQTT_THEME, "Failed to disconnect: {:?}", e);
+    }
+    cli.stop_consuming();
+}
+
+fn publish(cli: &mqtt::Client, msg: mqtt::Message) {
+    match cli.publish(msg.clone()) {
+        Ok(()) => info!(target: MQTT_THEME, "Published theme to '{}'", msg.topic()),
+        Err(e) => error!(
+            target: MQTT_THEME,
+            "Failed to publish theme to '{}': {:?}",
+            msg.topic(),
+            e
+        ),
+    }
+}
+
+impl Drop for ThemeSync {
+    fn drop(&mut self) {
+        self.shut_down();
+    }
+}
+
+impl ApollosKiosk {
+    /// Stop any running theme sync and start it again from the current config.
+    pub(crate) fn restart_theme_sync(&mut self, ctx: &egui::Context) {
+        let previous = self.theme_sync.take().and_then(|mut sync| sync.shut_down());
+
+        if self.config.mqtt_theme_sync {
+            self.theme_sync = ThemeSync::start(
+                &self.config,
+                self.theme_sync_id.clone(),
+                self.args.instance_name().as_deref(),
+                self.theme_tx.clone(),
+                ctx.clone(),
+                previous,
+            );
+        }
+    }
+
+    /// Show the theme sync connection state, repainting while it may change.
+    pub(crate) fn show_theme_sync_status(&self, ui: &mut egui::Ui) {
+        match &self.theme_sync {
+            Some(sync) => {
+                let status = sync.status();
+                status.show(ui);
+                if status != SyncStatus::Connected {
+                    ui.ctx().request_repaint_after(Duration::from_secs(1));
+                }
+            }
+            None if self.config.mqtt_theme_sync => {
+                SyncStatus::Failed("could not create client".to_string()).show(ui)
+            }
+            None => {
+                ui.label(egui::RichText::new("Not syncing").weak());
+            }
+        }
+    }
+
+    /// Map a kiosk theme name to the name the other neiam apps use.
+    ///
+    /// Prefers an alias that is the dashed lowercase form of the name (e.g.
+    /// "After Dark" -> "after-dark"), then any other alias, then the name itself.
+    fn sync_theme_name(&self, kiosk_name: &str) -> String {
+        let dashed = kiosk_name.to_lowercase().replace(' ', "-");
+
+        let mut aliases = theme::default_theme_aliases();
+        aliases.extend(self.config.theme_aliases.clone());
+        let mut candidates: Vec<&String> = aliases
+            .iter()
+            .filter(|(_, name)| name.as_str() == kiosk_name)
+            .map(|(alias, _)| alias)
+            .collect();
+        candidates.sort();
+
+        if candidates.contains(&&dashed) {
+            return dashed;
+        }
+        candidates
+            .first()
+            .map(|alias| alias.to_string())
+            .unwrap_or_else(|| kiosk_name.to_string())
+    }
+
+    /// Switch to a theme chosen on this kiosk and share the choice over MQTT.
+    pub(crate) fn select_theme(&mut self, ctx: &egui::Context, name: String) {
+        self.current_theme = name;
+        self.apply_theme(ctx);
+        self.save_config();
+
+        if self.theme_sync.is_none() {
+            return;
+        }
+
+        let Some(theme) = self
+            .themes
+            .iter()
+            .find(|t| t.name == self.current_theme)
+            .cloned()
+        else {
+            return;
+        };
+        let sync_name = self.sync_theme_name(&theme.name);
+        let is_builtin = theme.path.is_none() && !theme.remote;
+        if let Some(sync) = &self.theme_sync {
+            sync.publish(&sync_name, (!is_builtin).then_some(&theme));
+        }
+    }
+}
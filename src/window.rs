@@ -0,0 +1,217 @@
+use crate::logging::DISPLAY;
+use crate::{ApollosKiosk, Args};
+use eframe::egui;
+use log::{info, warn};
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+/// Build the native viewport from the window-related command line flags.
+pub(crate) fn viewport_builder(args: &Args) -> egui::ViewportBuilder {
+    let mut viewport = egui::ViewportBuilder::default()
+        .with_inner_size([1920.0, 1080.0])
+        .with_fullscreen(args.fullscreen)
+        .with_decorations(!args.borderless);
+
+    // Fullscreen always uses the monitor the window is on, so placing the window
+    // at a monitor's origin is how a particular display is selected.
+    if let Some(position) = &args.window_position {
+        viewport = viewport.with_position(*position);
+    }
+
+    if args.prevent_close {
+        viewport = viewport
+            .with_close_button(false)
+            .with_minimize_button(false);
+    }
+
+    viewport
+}
+
+/// Parse a window position given as `X,Y`.
+pub(crate) fn parse_position(s: &str) -> Result<egui::Pos2, String> {
+    let (x, y) = s
+        .split_once(',')
+        .ok_or_else(|| format!("expected X,Y but got '{}'", s))?;
+    let x = x.trim().parse::<f32>().map_err(|e| e.to_string())?;
+    let y = y.trim().parse::<f32>().map_err(|e| e.to_string())?;
+    Ok(egui::pos2(x, y))
+}
+
+/// Parse a resolution given as `WIDTHxHEIGHT`.
+pub(crate) fn parse_resolution(s: &str) -> Result<egui::Vec2, String> {
+    let (w, h) = s
+        .split_once(['x', 'X'])
+        .ok_or_else(|| format!("expected WIDTHxHEIGHT but got '{}'", s))?;
+    let w = w.trim().parse::<f32>().map_err(|e| e.to_string())?;
+    let h = h.trim().parse::<f32>().map_err(|e| e.to_string())?;
+    if w <= 0.0 || h <= 0.0 {
+        return Err("resolution must be positive".to_string());
+    }
+    Ok(egui::vec2(w, h))
+}
+
+/// Limits for the user scale, so a bad value in the config can't shrink the
+/// layout to nothing or blow it up past the screen.
+const MIN_SCALE: f32 = 0.1;
+const MAX_SCALE: f32 = 10.0;
+
+/// Parse `--scale`, which must be a positive number.
+pub(crate) fn parse_scale(s: &str) -> Result<f32, String> {
+    let scale = s.trim().parse::<f32>().map_err(|e| e.to_string())?;
+    if !scale.is_finite() || scale <= 0.0 {
+        return Err("scale must be a positive number".to_string());
+    }
+    Ok(scale)
+}
+
+/// The configured scale, kept within limits.
+pub(crate) fn clamp_scale(scale: f32) -> f32 {
+    if scale.is_finite() {
+        scale.clamp(MIN_SCALE, MAX_SCALE)
+    } else {
+        1.0
+    }
+}
+
+/// Keeps the display awake for as long as it is alive.
+///
+/// Holds a logind idle inhibitor via `systemd-inhibit`, and on X11 also turns
+/// off the screensaver and DPMS blanking.
+///
+/// Dropping it, including while unwinding from a panic, restores the X11
+/// settings and releases the inhibitor. The inhibitor also ends by itself when
+/// the kiosk exits without dropping it.
+pub(crate) struct SleepInhibitor {
+    child: Option<Child>,
+    saved_xset: Option<XsetState>,
+}
+
+/// X11 screensaver and DPMS settings from before the kiosk changed them.
+struct XsetState {
+    saver_timeout: u32,
+    dpms: bool,
+}
+
+impl XsetState {
+    fn query() -> Option<Self> {
+        let output = Command::new("xset").arg("q").output().ok()?;
+        let text = String::from_utf8_lossy(&output.stdout);
+        // "  timeout:  600    cycle:  600"
+        let saver_timeout = text
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("timeout:"))?
+            .split_whitespace()
+            .next()?
+            .parse()
+            .ok()?;
+        let dpms = text.contains("DPMS is Enabled");
+        Some(XsetState {
+            saver_timeout,
+            dpms,
+        })
+    }
+
+    fn restore(&self) {
+        let saver = match self.saver_timeout {
+            0 => "off".to_string(),
+            timeout => timeout.to_string(),
+        };
+        let dpms = if self.dpms { "+dpms" } else { "-dpms" };
+        match Command::new("xset").args(["s", &saver, dpms]).status() {
+            Ok(status) if status.success() => {
+                info!(target: DISPLAY, "Restored X11 screensaver and DPMS")
+            }
+            Ok(status) => warn!(target: DISPLAY, "xset exited with {}", status),
+            Err(e) => warn!(target: DISPLAY, "Failed to run xset: {}", e),
+        }
+    }
+}
+
+impl SleepInhibitor {
+    pub(crate) fn start() -> Self {
+        let mut saved_xset = None;
+        if std::env::var_os("DISPLAY").is_some() {
+            let before = XsetState::query();
+            match Command::new("xset").args(["s", "off", "-dpms"]).status() {
+                Ok(status) if status.success() => {
+                    info!(target: DISPLAY, "Disabled X11 screensaver and DPMS");
+                    saved_xset = before;
+                }
+                Ok(status) => warn!(target: DISPLAY, "xset exited with {}", status),
+                Err(e) => warn!(target: DISPLAY, "Failed to run xset: {}", e),
+            }
+        }
+
+        // Block for as long as this process lives, so the inhibitor isn't left
+        // behind if the kiosk is killed
+        let pid = std::process::id().to_string();
+        let child = Command::new("systemd-inhibit")
+            .args([
+                "--what=idle",
+                "--who=apollos-kiosk",
+                "--why=Kiosk display must stay on",
+                "--mode=block",
+                "tail",
+                "--pid",
+                &pid,
+                "-f",
+                "/dev/null",
+            ])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .spawn();
+
+        let child = match child {
+            Ok(child) => {
+                info!(target: DISPLAY, "Holding idle inhibitor");
+                Some(child)
+            }
+            Err(e) => {
+                warn!(target: DISPLAY, "Failed to start systemd-inhibit: {}", e);
+                None
+            }
+        };
+        Self { child, saved_xset }
+    }
+}
+
+impl Drop for SleepInhibitor {
+    fn drop(&mut self) {
+        if let Some(child) = &mut self.child {
+            let _ = child.kill();
+            let _ = child.wait();
+            info!(target: DISPLAY, "Released idle inhibitor");
+        }
+        if let Some(saved) = &self.saved_xset {
+            saved.restore();
+        }
+    }
+}
+
+impl ApollosKiosk {
+    /// Per-frame window handling: cursor auto-hide and close prevention.
+    pub(crate) fn update_window(&mut self, ctx: &egui::Context) {
+        if self.args.prevent_close && ctx.input(|i| i.viewport().close_requested()) {
+            info!(target: DISPLAY, "Ignoring close request");
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+        }
+
+        if let Some(secs) = self.args.hide_cursor_after {
+            let timeout = secs as f32;
+            let idle = ctx.input(|i| {
+                if i.pointer.any_down() {
+                    0.0
+                } else {
+                    i.pointer.time_since_last_movement()
+                }
+            });
+
+            if idle >= timeout {
+                ctx.set_cursor_icon(egui::CursorIcon::None);
+            } else {
+                // Wake up again once the cursor should disappear
+                ctx.request_repaint_after(Duration::from_secs_f32(timeout - idle));
+            }
+        }
+    }
+}